@@ -0,0 +1,168 @@
+//! What helpers do when the Toxiproxy server is not running, so a suite with chaos tests can
+//! still run on machines without `toxiproxy-server` while CI enforces the real thing.
+//!
+//! The policy is set with [`ClientBuilder::absent_server`] or the `TOXIPROXY_ABSENT_SERVER`
+//! environment variable (`fail`, `skip` or `pass-through`), which is how `TOXIPROXY` picks it.
+//! Whether the server is running is checked when it matters, reusing the result for a second
+//! so a server started or stopped during the run is noticed.
+//!
+//! # Examples
+//!
+//! ```
+//! use toxiproxy_rust::skip_if_absent;
+//!
+//! fn redis_survives_latency() {
+//!     // Returns early when the server is absent and the policy is `skip`.
+//!     skip_if_absent!();
+//!
+//!     /* ... */
+//! }
+//! # redis_survives_latency();
+//! ```
+//!
+//! [`ClientBuilder::absent_server`]: ../client/struct.ClientBuilder.html#method.absent_server
+
+use super::proxy::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Policy applied when the Toxiproxy server is not running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AbsentServer {
+    /// Calls fail with connection errors, as they would without a policy.
+    #[default]
+    Fail,
+    /// Tests guarded by [`skip_if_absent!`] or `#[toxiproxy_test]` return early, with a message
+    /// on stderr. Other calls fail, saying the server is absent: this includes helpers like
+    /// [`Client::find_and_reset_proxy`] or [`Fixture::new`], so tests using them directly need
+    /// the guard to be skipped.
+    ///
+    /// [`skip_if_absent!`]: ../macro.skip_if_absent.html
+    /// [`Client::find_and_reset_proxy`]: ../client/struct.Client.html#method.find_and_reset_proxy
+    /// [`Fixture::new`]: ../fixture/struct.Fixture.html#method.new
+    Skip,
+    /// Proxies created by `populate` point at their upstream directly, and changing them
+    /// (toxics, enabling, disabling) does nothing: tests run without faults.
+    PassThrough,
+}
+
+impl FromStr for AbsentServer {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fail" => Ok(AbsentServer::Fail),
+            "skip" => Ok(AbsentServer::Skip),
+            "pass-through" | "passthrough" | "pass_through" => Ok(AbsentServer::PassThrough),
+            _ => Err(format!(
+                "unknown absent server policy `{}`, expected `fail`, `skip` or `pass-through`",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AbsentServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            AbsentServer::Fail => "fail",
+            AbsentServer::Skip => "skip",
+            AbsentServer::PassThrough => "pass-through",
+        })
+    }
+}
+
+/// Value a test returns when it is skipped: `()` or `Ok` of it.
+pub trait Skipped {
+    fn skipped() -> Self;
+}
+
+impl Skipped for () {
+    fn skipped() -> Self {}
+}
+
+impl<T, E> Skipped for Result<T, E>
+where
+    T: Skipped,
+{
+    fn skipped() -> Self {
+        Ok(T::skipped())
+    }
+}
+
+/// Returns early from the current test if the Toxiproxy server is absent and the policy is
+/// [`AbsentServer::Skip`]. Uses `TOXIPROXY` unless given a client. The test must return `()`
+/// or a `Result` of it.
+///
+/// # Examples
+///
+/// ```
+/// # use toxiproxy_rust::{absent_server::AbsentServer, client::Client, skip_if_absent};
+/// fn test_with_client() -> Result<(), String> {
+///     let client = Client::builder("127.0.0.1:1").absent_server(AbsentServer::Skip).build()?;
+///     skip_if_absent!(client);
+///
+///     Err("not skipped".into())
+/// }
+///
+/// assert_eq!(Ok(()), test_with_client());
+/// ```
+///
+/// [`AbsentServer::Skip`]: absent_server/enum.AbsentServer.html#variant.Skip
+#[macro_export]
+macro_rules! skip_if_absent {
+    () => {
+        $crate::skip_if_absent!($crate::TOXIPROXY)
+    };
+    ($client:expr) => {
+        if $client.skip_if_absent() {
+            return $crate::absent_server::Skipped::skipped();
+        }
+    };
+}
+
+/// Proxies created while passing traffic through, standing in for the server's.
+#[derive(Debug, Default)]
+pub(crate) struct PassThroughProxies {
+    proxy_packs: Mutex<HashMap<String, ProxyPack>>,
+}
+
+impl PassThroughProxies {
+    /// Registers a proxy listening on its upstream address, without toxics.
+    pub(crate) fn add(&self, proxy_pack: ProxyPack) -> ProxyPack {
+        let proxy_pack = ProxyPack::new(
+            proxy_pack.name,
+            proxy_pack.upstream.clone(),
+            proxy_pack.upstream,
+        );
+        self.lock()
+            .insert(proxy_pack.name.clone(), proxy_pack.clone());
+        proxy_pack
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<ProxyPack, String> {
+        self.lock().get(name).cloned().ok_or_else(|| {
+            format!(
+                "proxy `{}` not found: the Toxiproxy server is absent, only proxies created by \
+                 `populate` pass traffic through",
+                name
+            )
+        })
+    }
+
+    pub(crate) fn all(&self) -> HashMap<String, ProxyPack> {
+        self.lock().clone()
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.lock().remove(name);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, ProxyPack>> {
+        self.proxy_packs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
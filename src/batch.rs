@@ -0,0 +1,259 @@
+//! A sequence of changes to several proxies, applied as a whole: if a step fails, the steps
+//! already applied are undone.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! # use toxiproxy_rust::{proxy::ProxyPack, toxic::{Stream, ToxicPack, Toxicity}, TOXIPROXY};
+//! # TOXIPROXY.populate(vec![ProxyPack::new(
+//! #     "socket".into(),
+//! #     "localhost:2001".into(),
+//! #     "localhost:2000".into(),
+//! # )]).unwrap();
+//! let mut attributes = HashMap::new();
+//! attributes.insert("latency".into(), 1000.into());
+//!
+//! TOXIPROXY
+//!     .batch()
+//!     .add_toxic(
+//!         "socket",
+//!         ToxicPack::new("latency".into(), Stream::Downstream, Toxicity::always(), attributes),
+//!     )
+//!     .disable("socket")
+//!     .run(|| {
+//!         /* the service is down, and slow once back */
+//!     })
+//!     .expect("batch is applied and undone");
+//! ```
+
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// A change to a proxy.
+#[derive(Debug, Clone)]
+enum Operation {
+    Enable(String),
+    Disable(String),
+    AddToxic(String, ToxicPack),
+    DeleteToxic(String, String),
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Enable(proxy) => write!(f, "enable `{}`", proxy),
+            Operation::Disable(proxy) => write!(f, "disable `{}`", proxy),
+            Operation::AddToxic(proxy, toxic) => {
+                write!(f, "add toxic `{}` to `{}`", toxic.name, proxy)
+            }
+            Operation::DeleteToxic(proxy, toxic_name) => {
+                write!(f, "delete toxic `{}` from `{}`", toxic_name, proxy)
+            }
+        }
+    }
+}
+
+/// What it takes to revert an applied change.
+#[derive(Debug)]
+enum Undo {
+    Enable(Proxy),
+    Disable(Proxy),
+    DeleteToxics(Proxy, Vec<String>),
+    AddToxic(Proxy, ToxicPack),
+}
+
+impl Undo {
+    /// Reverts the change. The toxics already deleted are forgotten, so running it again
+    /// after a failure only retries the rest.
+    fn run(&mut self) -> Result<(), String> {
+        match self {
+            Undo::Enable(proxy) => proxy.enable(),
+            Undo::Disable(proxy) => proxy.disable(),
+            Undo::DeleteToxics(proxy, toxic_names) => {
+                while let Some(toxic_name) = toxic_names.first() {
+                    proxy.delete_toxic(toxic_name)?;
+                    toxic_names.remove(0);
+                }
+                Ok(())
+            }
+            Undo::AddToxic(proxy, toxic) => proxy.add_toxic(toxic.clone()).map(|_| ()),
+        }
+    }
+}
+
+/// Builder of a batch of changes, created by [`Client::batch`]. Nothing is sent to the server
+/// until the batch is applied.
+///
+/// [`Client::batch`]: ../client/struct.Client.html#method.batch
+#[derive(Debug)]
+pub struct Batch {
+    client: Client,
+    operations: Vec<Operation>,
+}
+
+impl Batch {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            operations: vec![],
+        }
+    }
+
+    /// Enables a proxy.
+    pub fn enable(mut self, proxy: &str) -> Self {
+        self.operations.push(Operation::Enable(proxy.into()));
+        self
+    }
+
+    /// Disables a proxy, taking its service down.
+    pub fn disable(mut self, proxy: &str) -> Self {
+        self.operations.push(Operation::Disable(proxy.into()));
+        self
+    }
+
+    /// Adds a toxic to a proxy.
+    pub fn add_toxic(mut self, proxy: &str, toxic: ToxicPack) -> Self {
+        self.operations
+            .push(Operation::AddToxic(proxy.into(), toxic));
+        self
+    }
+
+    /// Removes a toxic of a proxy by name.
+    pub fn delete_toxic(mut self, proxy: &str, toxic_name: &str) -> Self {
+        self.operations
+            .push(Operation::DeleteToxic(proxy.into(), toxic_name.into()));
+        self
+    }
+
+    /// Applies the changes in order. If one fails, the ones already applied are undone in
+    /// reverse order, and the error names the failed step along with any undo failure.
+    pub fn apply(self) -> Result<AppliedBatch, String> {
+        for (index, operation) in self.operations.iter().enumerate() {
+            if let Operation::AddToxic(_, toxic) = operation {
+                toxic.validate().map_err(|err| {
+                    format!(
+                        "batch step {} (`{}`) is invalid: {}",
+                        index + 1,
+                        operation,
+                        err
+                    )
+                })?;
+            }
+        }
+
+        let mut applied = AppliedBatch { undo: vec![] };
+        for (index, operation) in self.operations.iter().enumerate() {
+            if let Err(err) = self.run_operation(operation, &mut applied.undo) {
+                let err = format!("batch step {} (`{}`) failed: {}", index + 1, operation, err);
+
+                return Err(match applied.undo() {
+                    Ok(()) => err,
+                    Err(undo_err) => format!("{}; {}", err, undo_err),
+                });
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// Applies the changes, runs `closure`, then undoes them. If `closure` panics, the changes
+    /// are undone before the panic is resumed.
+    pub fn run<F>(self, closure: F) -> Result<(), String>
+    where
+        F: FnOnce(),
+    {
+        let applied = self.apply()?;
+        let result = panic::catch_unwind(AssertUnwindSafe(closure));
+        let undone = applied.undo();
+        match result {
+            Ok(()) => undone,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+
+    /// Runs a change, recording how to undo each of its effects as soon as it took place.
+    fn run_operation(&self, operation: &Operation, undo: &mut Vec<Undo>) -> Result<(), String> {
+        match operation {
+            Operation::Enable(name) => {
+                let proxy = self.client.find_proxy(name)?;
+                if !proxy.is_enabled() {
+                    proxy.enable()?;
+                    undo.push(Undo::Disable(proxy));
+                }
+            }
+            Operation::Disable(name) => {
+                let proxy = self.client.find_proxy(name)?;
+                if proxy.is_enabled() {
+                    proxy.disable()?;
+                    undo.push(Undo::Enable(proxy));
+                }
+            }
+            Operation::AddToxic(name, toxic) => {
+                // Directions of `Stream::Both` are added one at a time, so a failure on the
+                // second one still undoes the first.
+                for toxic in toxic.clone().split() {
+                    let proxy = self.client.find_proxy(name)?;
+                    let created = proxy.add_toxic(toxic)?;
+                    let toxic_names = created.into_iter().map(|toxic| toxic.name).collect();
+                    undo.push(Undo::DeleteToxics(proxy, toxic_names));
+                }
+            }
+            Operation::DeleteToxic(name, toxic_name) => {
+                let proxy = self.client.find_proxy(name)?;
+                let toxic = proxy
+                    .proxy_pack()
+                    .toxics
+                    .into_iter()
+                    .find(|toxic| &toxic.name == toxic_name)
+                    .ok_or_else(|| format!("proxy `{}` has no toxic `{}`", name, toxic_name))?;
+                proxy.delete_toxic(toxic_name)?;
+                undo.push(Undo::AddToxic(proxy, toxic));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Changes of a [`Batch`] that were applied, to be undone when no longer needed.
+///
+/// [`Batch`]: struct.Batch.html
+#[derive(Debug)]
+#[must_use = "the changes stay applied unless undone"]
+pub struct AppliedBatch {
+    undo: Vec<Undo>,
+}
+
+impl AppliedBatch {
+    /// Reverts the changes in reverse order. Every step is attempted even if one fails, the
+    /// error lists all the failures.
+    pub fn undo(mut self) -> Result<(), String> {
+        self.try_undo()
+    }
+
+    /// Like [`undo`], but keeps the steps that failed, so calling it again retries them.
+    ///
+    /// [`undo`]: #method.undo
+    pub fn try_undo(&mut self) -> Result<(), String> {
+        let mut errors = vec![];
+        let mut failed = vec![];
+        while let Some(mut undo) = self.undo.pop() {
+            if let Err(err) = undo.run() {
+                errors.push(err);
+                failed.push(undo);
+            }
+        }
+        failed.reverse();
+        self.undo = failed;
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("undo failed: {}", errors.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Clocks measuring the delays of in-process toxics, in [`ToxicStream`] and the HTTP fault
+//! layer, so tests don't have to wait for them in real time.
+//!
+//! - [`SystemClock`]: real time, the default
+//! - [`TokioClock`]: tokio's time, which stands still under `tokio::time::pause` and jumps
+//!   to the next timer when the runtime is idle (`tokio` feature, async code only)
+//! - [`VirtualClock`]: time that jumps to the deadline as soon as anything waits for it, so
+//!   a 30 seconds timeout completes instantly, and only moves otherwise with
+//!   [`VirtualClock::advance`]
+//!
+//! With a virtual clock and a seed for the random draws (toxicity, jitter, slice sizes), the
+//! same test always sees the same delays.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::{collections::HashMap, io::{Cursor, Read}, time::Duration};
+//! use toxiproxy_rust::clock::VirtualClock;
+//! use toxiproxy_rust::toxic::{Stream, ToxicPack, Toxicity};
+//! use toxiproxy_rust::toxic_stream::ToxicStream;
+//!
+//! let mut attributes = HashMap::new();
+//! attributes.insert("timeout".into(), 30_000.into());
+//! let timeout = ToxicPack::new("timeout".into(), Stream::Downstream, Toxicity::always(), attributes);
+//!
+//! let clock = VirtualClock::new();
+//! let mut stream = ToxicStream::builder(Cursor::new(b"hello".to_vec()), vec![timeout])
+//!     .clock(clock.clone())
+//!     .build()
+//!     .unwrap();
+//!
+//! // Returns at once, with the stream closed by the timeout.
+//! assert_eq!(0, stream.read(&mut [0; 5]).unwrap());
+//! assert_eq!(Duration::from_secs(30), clock.elapsed());
+//! ```
+//!
+//! [`ToxicStream`]: ../toxic_stream/struct.ToxicStream.html
+//! [`SystemClock`]: struct.SystemClock.html
+//! [`TokioClock`]: struct.TokioClock.html
+//! [`VirtualClock`]: struct.VirtualClock.html
+//! [`VirtualClock::advance`]: struct.VirtualClock.html#method.advance
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Timer of a [`Clock`], for async code.
+///
+/// [`Clock`]: trait.Clock.html
+#[cfg(feature = "tokio")]
+pub type Timer = Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+
+/// Source of the current time and of waits for in-process toxics.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+
+    /// Blocks the thread until `deadline`.
+    fn sleep_until(&self, deadline: Instant);
+
+    /// A timer completing at `deadline`, or `None` if the clock got there without waiting.
+    /// Uses tokio's timers by default.
+    #[cfg(feature = "tokio")]
+    fn timer(&self, deadline: Instant) -> Option<Timer> {
+        Some(Box::pin(tokio::time::sleep_until(
+            tokio::time::Instant::from_std(deadline),
+        )))
+    }
+}
+
+/// Real time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Tokio's time, following `tokio::time::pause` and `advance`. Meant for async streams:
+/// blocking waits sleep in real time, which a paused clock doesn't see.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        thread::sleep(deadline.saturating_duration_since(self.now()));
+    }
+}
+
+/// Time that only moves when something waits for it or when advanced, shared by its clones.
+#[derive(Debug, Clone)]
+pub struct VirtualClock {
+    started: Instant,
+    now: Arc<Mutex<Instant>>,
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            now: Arc::new(Mutex::new(started)),
+        }
+    }
+
+    /// Moves the time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.lock() += duration;
+    }
+
+    /// Time gone by on this clock since it was created.
+    pub fn elapsed(&self) -> Duration {
+        *self.lock() - self.started
+    }
+
+    fn advance_to(&self, deadline: Instant) {
+        let mut now = self.lock();
+        *now = (*now).max(deadline);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Instant> {
+        self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        *self.lock()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        self.advance_to(deadline);
+    }
+
+    #[cfg(feature = "tokio")]
+    fn timer(&self, deadline: Instant) -> Option<Timer> {
+        self.advance_to(deadline);
+        None
+    }
+}
+
+/// A timer armed for a deadline, re-armed when the deadline changes.
+#[cfg(feature = "tokio")]
+#[derive(Default)]
+pub(crate) struct Alarm {
+    armed: Option<(Instant, Timer)>,
+}
+
+#[cfg(feature = "tokio")]
+impl Alarm {
+    /// Polls until `deadline` on `clock`.
+    pub(crate) fn poll_until(
+        &mut self,
+        clock: &dyn Clock,
+        deadline: Instant,
+        cx: &mut Context<'_>,
+    ) -> Poll<()> {
+        if clock.now() >= deadline {
+            self.armed = None;
+            return Poll::Ready(());
+        }
+
+        match &self.armed {
+            Some((armed_for, _)) if *armed_for == deadline => {}
+            _ => self.armed = clock.timer(deadline).map(|timer| (deadline, timer)),
+        }
+
+        let fired = match &mut self.armed {
+            Some((_, timer)) => timer.as_mut().poll(cx).is_ready(),
+            None => true,
+        };
+        if !fired {
+            return Poll::Pending;
+        }
+
+        self.armed = None;
+        Poll::Ready(())
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl fmt::Debug for Alarm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alarm")
+            .field(
+                "armed_for",
+                &self.armed.as_ref().map(|(deadline, _)| deadline),
+            )
+            .finish()
+    }
+}
@@ -1,2 +1,13 @@
-pub const ERR_LOCK: &str = "Lock cannot be granted";
 pub const ERR_JSON_SERIALIZE: &str = "JSON serialization failed";
+
+pub const ENV_URL: &str = "TOXIPROXY_URL";
+pub const ENV_HOST: &str = "TOXIPROXY_HOST";
+pub const ENV_PORT: &str = "TOXIPROXY_PORT";
+pub const ENV_TIMEOUT_MS: &str = "TOXIPROXY_TIMEOUT_MS";
+pub const ENV_CONNECT_TIMEOUT_MS: &str = "TOXIPROXY_CONNECT_TIMEOUT_MS";
+pub const ENV_RETRIES: &str = "TOXIPROXY_RETRIES";
+pub const ENV_ABSENT_SERVER: &str = "TOXIPROXY_ABSENT_SERVER";
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+/// Port of the server's API when the address doesn't name one.
+pub const DEFAULT_PORT: u16 = 8474;
@@ -0,0 +1,405 @@
+//! Runs the same check under a list of fault conditions and reports how it fared in each.
+//!
+//! Every case is a set of faults (toxics, or proxies taken down) applied to the proxies before
+//! the check runs. The proxies named by any case are reset before each case and after the
+//! last one, so the cases don't leak into each other. The check passes by returning `Ok`, and
+//! fails by returning an error or panicking.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! # use toxiproxy_rust::{fault_matrix::Fault, proxy::ProxyPack, toxic::{Stream, ToxicPack, Toxicity}, TOXIPROXY};
+//! # TOXIPROXY.populate(vec![ProxyPack::new(
+//! #     "socket".into(),
+//! #     "localhost:2001".into(),
+//! #     "localhost:2000".into(),
+//! # )]).unwrap();
+//! let mut attributes = HashMap::new();
+//! attributes.insert("latency".into(), 1000.into());
+//! let latency = ToxicPack::new("latency".into(), Stream::Downstream, Toxicity::always(), attributes);
+//!
+//! let report = TOXIPROXY
+//!     .fault_matrix("socket_client")
+//!     .case("baseline", vec![])
+//!     .case("down", vec![Fault::down("socket")])
+//!     .case("slow", vec![Fault::toxic("socket", latency)])
+//!     .run(|case| -> Result<(), String> {
+//!         /* call the service, return an error or panic if it misbehaves */
+//!         Ok(())
+//!     });
+//!
+//! println!("{}", report);
+//! let junit = report.to_junit();
+//! assert!(report.all_passed());
+//! ```
+
+use super::batch::*;
+use super::client::*;
+use super::toxic::*;
+use serde_json::json;
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+/// A fault applied to a proxy for the duration of a case.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// The proxy is disabled: connections are refused.
+    Down(String),
+    /// A toxic is added to the proxy.
+    Toxic(String, ToxicPack),
+}
+
+impl Fault {
+    pub fn down(proxy: &str) -> Self {
+        Fault::Down(proxy.into())
+    }
+
+    pub fn toxic(proxy: &str, toxic: ToxicPack) -> Self {
+        Fault::Toxic(proxy.into(), toxic)
+    }
+
+    /// Name of the proxy the fault is applied to.
+    pub fn proxy(&self) -> &str {
+        match self {
+            Fault::Down(proxy) | Fault::Toxic(proxy, _) => proxy,
+        }
+    }
+}
+
+/// How the check fared in a case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    /// The check returned an error.
+    Failed(String),
+    /// The check panicked, with the panic message.
+    Panicked(String),
+    /// The case could not be set up (a proxy could not be reset or a fault applied), the
+    /// check did not run.
+    Error(String),
+}
+
+impl Outcome {
+    pub fn is_passed(&self) -> bool {
+        *self == Outcome::Passed
+    }
+
+    /// Short name of the outcome, as used in reports.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "passed",
+            Outcome::Failed(_) => "failed",
+            Outcome::Panicked(_) => "panicked",
+            Outcome::Error(_) => "error",
+        }
+    }
+
+    /// Error or panic message, if any.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Outcome::Passed => None,
+            Outcome::Failed(message) | Outcome::Panicked(message) | Outcome::Error(message) => {
+                Some(message)
+            }
+        }
+    }
+}
+
+/// Result of one case.
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: Outcome,
+    /// Time the check took, zero if it did not run.
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct Case {
+    name: String,
+    faults: Vec<Fault>,
+}
+
+/// Builder of the cases of a matrix, created by [`Client::fault_matrix`].
+///
+/// [`Client::fault_matrix`]: ../client/struct.Client.html#method.fault_matrix
+#[derive(Debug)]
+pub struct FaultMatrix {
+    client: Client,
+    name: String,
+    cases: Vec<Case>,
+}
+
+impl FaultMatrix {
+    pub(crate) fn new(client: Client, name: &str) -> Self {
+        Self {
+            client,
+            name: name.into(),
+            cases: vec![],
+        }
+    }
+
+    /// Adds a case, run with all of `faults` applied. A case without faults runs the check
+    /// against healthy proxies.
+    pub fn case(mut self, name: &str, faults: Vec<Fault>) -> Self {
+        self.cases.push(Case {
+            name: name.into(),
+            faults,
+        });
+        self
+    }
+
+    /// Runs `check` once per case, in order, given the name of the case. A failing case
+    /// doesn't stop the following ones.
+    pub fn run<F, E>(self, mut check: F) -> FaultReport
+    where
+        F: FnMut(&str) -> Result<(), E>,
+        E: fmt::Display,
+    {
+        let mut proxies = self
+            .cases
+            .iter()
+            .flat_map(|case| case.faults.iter().map(Fault::proxy))
+            .collect::<Vec<&str>>();
+        proxies.sort_unstable();
+        proxies.dedup();
+
+        let results = self
+            .cases
+            .iter()
+            .map(|case| self.run_case(&proxies, case, &mut check))
+            .collect();
+        let _ = self.reset(&proxies);
+
+        FaultReport {
+            name: self.name,
+            results,
+        }
+    }
+
+    fn run_case<F, E>(&self, proxies: &[&str], case: &Case, check: &mut F) -> CaseResult
+    where
+        F: FnMut(&str) -> Result<(), E>,
+        E: fmt::Display,
+    {
+        let mut result = CaseResult {
+            name: case.name.clone(),
+            outcome: Outcome::Passed,
+            duration: Duration::ZERO,
+        };
+
+        let applied = match self.set_up(proxies, case) {
+            Ok(applied) => applied,
+            Err(err) => {
+                result.outcome = Outcome::Error(err);
+                return result;
+            }
+        };
+
+        let started = Instant::now();
+        result.outcome = match panic::catch_unwind(AssertUnwindSafe(|| check(&case.name))) {
+            Ok(Ok(())) => Outcome::Passed,
+            Ok(Err(err)) => Outcome::Failed(err.to_string()),
+            Err(panic) => Outcome::Panicked(panic_message(panic.as_ref())),
+        };
+        result.duration = started.elapsed();
+
+        // Faults left in place would leak into the next case, unless the reset removes them.
+        if let Err(err) = applied.undo() {
+            if result.outcome.is_passed() {
+                result.outcome = Outcome::Error(err);
+            }
+        }
+
+        result
+    }
+
+    /// Resets the proxies of the matrix, then applies the faults of the case.
+    fn set_up(&self, proxies: &[&str], case: &Case) -> Result<AppliedBatch, String> {
+        self.reset(proxies)?;
+
+        case.faults
+            .iter()
+            .fold(self.client.batch(), |batch, fault| match fault {
+                Fault::Down(proxy) => batch.disable(proxy),
+                Fault::Toxic(proxy, toxic) => batch.add_toxic(proxy, toxic.clone()),
+            })
+            .apply()
+    }
+
+    fn reset(&self, proxies: &[&str]) -> Result<(), String> {
+        proxies
+            .iter()
+            .try_for_each(|proxy| self.client.find_and_reset_proxy(proxy).map(|_| ()))
+    }
+}
+
+/// Outcomes of the cases of a [`FaultMatrix`]. Displays as a table.
+///
+/// [`FaultMatrix`]: struct.FaultMatrix.html
+#[derive(Debug, Clone)]
+pub struct FaultReport {
+    name: String,
+    results: Vec<CaseResult>,
+}
+
+impl FaultReport {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Results in the order the cases were added.
+    pub fn results(&self) -> &[CaseResult] {
+        &self.results
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.results.iter().all(|result| result.outcome.is_passed())
+    }
+
+    /// The report as JSON: the name of the matrix, the count of each outcome, and the cases
+    /// with their outcome, message and duration in seconds.
+    pub fn to_json(&self) -> String {
+        let count = |label: &str| {
+            self.results
+                .iter()
+                .filter(|result| result.outcome.label() == label)
+                .count()
+        };
+        let cases = self
+            .results
+            .iter()
+            .map(|result| {
+                json!({
+                    "name": result.name,
+                    "outcome": result.outcome.label(),
+                    "message": result.outcome.message(),
+                    "duration_secs": result.duration.as_secs_f64(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "name": self.name,
+            "passed": count("passed"),
+            "failed": count("failed"),
+            "panicked": count("panicked"),
+            "errors": count("error"),
+            "cases": cases,
+        })
+        .to_string()
+    }
+
+    /// The report as a JUnit XML test suite, one test case per case. Errors and panics of the
+    /// check are failures, cases that could not be set up are errors.
+    pub fn to_junit(&self) -> String {
+        let failures = self
+            .results
+            .iter()
+            .filter(|result| matches!(result.outcome, Outcome::Failed(_) | Outcome::Panicked(_)))
+            .count();
+        let errors = self
+            .results
+            .iter()
+            .filter(|result| matches!(result.outcome, Outcome::Error(_)))
+            .count();
+        let total_time = self
+            .results
+            .iter()
+            .map(|result| result.duration)
+            .sum::<Duration>();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&self.name),
+            self.results.len(),
+            failures,
+            errors,
+            total_time.as_secs_f64()
+        ));
+        for result in &self.results {
+            xml.push_str(&format!(
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&result.name),
+                escape_xml(&self.name),
+                result.duration.as_secs_f64()
+            ));
+
+            let element = match result.outcome {
+                Outcome::Passed => None,
+                Outcome::Failed(_) | Outcome::Panicked(_) => Some("failure"),
+                Outcome::Error(_) => Some("error"),
+            };
+            match (element, result.outcome.message()) {
+                (Some(element), Some(message)) => xml.push_str(&format!(
+                    ">\n    <{element} type=\"{}\" message=\"{}\"/>\n  </testcase>\n",
+                    result.outcome.label(),
+                    escape_xml(message),
+                    element = element
+                )),
+                _ => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("</testsuite>\n");
+
+        xml
+    }
+}
+
+impl fmt::Display for FaultReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .results
+            .iter()
+            .map(|result| result.name.chars().count())
+            .chain(Some("case".len()))
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:<width$}  {:<8}  {:>10}  message",
+            "case", "outcome", "duration"
+        )?;
+        for result in &self.results {
+            writeln!(
+                f,
+                "{:<width$}  {:<8}  {:>10}  {}",
+                result.name,
+                result.outcome.label(),
+                format!("{:.1?}", result.duration),
+                result.outcome.message().unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic without a message".into())
+}
+
+/// Escapes markup characters and drops the ones XML 1.0 doesn't allow at all, like the
+/// terminal escapes of a panic message.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            '&' => Some("&amp;".into()),
+            '<' => Some("&lt;".into()),
+            '>' => Some("&gt;".into()),
+            '"' => Some("&quot;".into()),
+            '\'' => Some("&apos;".into()),
+            '\t' | '\n' | '\r' => Some(c.to_string()),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => None,
+            c => Some(c.to_string()),
+        })
+        .collect()
+}
@@ -0,0 +1,170 @@
+//! Proxies owned by a single test: created fresh before it runs and deleted afterwards, even if
+//! the test panics. This is what the `#[toxiproxy_test]` attribute (from the `macros` feature)
+//! expands to, and it can be used directly as well.
+//!
+//! Calls to the server are made from a separate thread, so setting up and tearing down a
+//! fixture is safe inside async tests, where the blocking HTTP client cannot run on the
+//! executor's thread.
+//!
+//! # Examples
+//!
+//! ```
+//! use toxiproxy_rust::{fixture::Fixture, TOXIPROXY};
+//!
+//! let fixture = Fixture::setup(&TOXIPROXY, "my_test", &["redis=6379"]).unwrap();
+//! let redis = fixture.proxy("redis");
+//! assert_eq!("my_test__redis", redis.name());
+//! assert_eq!("localhost:6379", redis.upstream());
+//! ```
+
+use super::client::*;
+use super::proxy::*;
+use super::TOXIPROXY;
+use std::thread;
+
+/// Proxies of a test, deleted from the server when dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    namespace: String,
+    client: Client,
+    proxies: Vec<(String, Proxy)>,
+}
+
+impl Fixture {
+    /// Creates the proxies on the server of `toxiproxy_rust::TOXIPROXY`, see [`setup`].
+    ///
+    /// [`setup`]: #method.setup
+    pub fn new(namespace: &str, proxies: &[&str]) -> Result<Self, String> {
+        off_thread(|| Self::setup(&TOXIPROXY, namespace, proxies))
+    }
+
+    /// Creates the proxies given as `name=port` (an upstream on `localhost`) or
+    /// `name=host:port`, named `{namespace}__{name}` and listening on ports picked by the
+    /// server. Existing proxies of the same name are reset: their toxics are removed and they
+    /// are enabled.
+    pub fn setup(client: &Client, namespace: &str, proxies: &[&str]) -> Result<Self, String> {
+        let namespace = sanitize(namespace);
+        let specs = proxies
+            .iter()
+            .map(|spec| parse_spec(spec))
+            .collect::<Result<Vec<(String, String)>, String>>()?;
+        let proxy_packs = specs
+            .iter()
+            .map(|(name, upstream)| {
+                ProxyPack::new(
+                    format!("{}__{}", namespace, name),
+                    "127.0.0.1:0".into(),
+                    upstream.clone(),
+                )
+            })
+            .collect();
+
+        let mut created = off_thread(|| client.populate(proxy_packs))?;
+        let proxies = specs
+            .into_iter()
+            .filter_map(|(name, _)| {
+                let full_name = format!("{}__{}", namespace, name);
+                let index = created.iter().position(|proxy| proxy.name() == full_name)?;
+                Some((name, created.swap_remove(index)))
+            })
+            .collect();
+        let fixture = Self {
+            namespace,
+            client: client.clone(),
+            proxies,
+        };
+
+        off_thread(|| {
+            fixture.proxies.iter().try_for_each(|(_, proxy)| {
+                proxy.delete_all_toxics()?;
+                proxy.enable()
+            })
+        })?;
+
+        Ok(fixture)
+    }
+
+    /// Proxy by the name it was given in `setup`, without the namespace.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the fixture has no such proxy.
+    pub fn proxy(&self, name: &str) -> &Proxy {
+        self.proxies
+            .iter()
+            .find(|(proxy_name, _)| proxy_name == name)
+            .map(|(_, proxy)| proxy)
+            .unwrap_or_else(|| panic!("fixture `{}` has no proxy named `{}`", self.namespace, name))
+    }
+
+    /// Prefix of the names of the proxies on the server.
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// The client the proxies were created with.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let proxies = &self.proxies;
+        off_thread(|| {
+            for (_, proxy) in proxies {
+                let _ = proxy.delete();
+            }
+        });
+    }
+}
+
+/// Splits `name=port` or `name=host:port` into the name and the upstream address.
+fn parse_spec(spec: &str) -> Result<(String, String), String> {
+    let invalid = || {
+        format!(
+            "invalid proxy `{}`: expected `name=port` or `name=host:port`",
+            spec
+        )
+    };
+
+    let (name, upstream) = spec.split_once('=').ok_or_else(invalid)?;
+    let (name, upstream) = (name.trim(), upstream.trim());
+    if name.is_empty() || upstream.is_empty() {
+        return Err(invalid());
+    }
+
+    let upstream = if upstream.parse::<u16>().is_ok() {
+        format!("localhost:{}", upstream)
+    } else {
+        upstream.to_owned()
+    };
+
+    Ok((name.to_owned(), upstream))
+}
+
+/// Makes a name safe to use in the server's URLs, like `my_crate::tests::redis_down` to
+/// `my_crate_tests_redis_down`.
+fn sanitize(namespace: &str) -> String {
+    let mut sanitized = String::with_capacity(namespace.len());
+    for c in namespace.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            sanitized.push(c);
+        } else if !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    sanitized
+}
+
+/// Runs `f` on a separate thread and waits for it, re-raising its panic.
+fn off_thread<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send,
+    T: Send,
+{
+    thread::scope(|scope| match scope.spawn(f).join() {
+        Ok(result) => result,
+        Err(panic) => std::panic::resume_unwind(panic),
+    })
+}
@@ -1,37 +1,95 @@
-use reqwest::{blocking::Client, blocking::Response, Url};
+use super::absent_server::*;
+use super::consts::*;
+use super::version::*;
+use super::wait::*;
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    StatusCode, Url,
+};
+use serde::Deserialize;
 use std::{
-    net::{SocketAddr, ToSocketAddrs},
-    str::FromStr,
+    sync::{Mutex, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
 };
 
+/// How long connecting to the API port may take before the server is considered absent.
+const ALIVE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the result of checking whether the server is running is reused, so a server
+/// started (or stopped) during a test run is noticed.
+const ABSENT_CHECK_TTL: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
 pub struct HttpClient {
     client: Client,
-    toxiproxy_addr: SocketAddr,
+    base_url: Result<Url, String>,
+    retries: u32,
+    retry_backoff: Duration,
+    /// Version of the server, `None` if it reports one that cannot be parsed (like a
+    /// development build).
+    server_version: OnceLock<Option<ServerVersion>>,
+    absent_server: AbsentServer,
+    /// Whether the server was found not running and when, checked again once
+    /// `ABSENT_CHECK_TTL` has passed if the policy needs it.
+    absent: Mutex<Option<(Instant, bool)>>,
+    pub(crate) pass_through: PassThroughProxies,
+}
+
+/// Error body returned by the server.
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: String,
 }
 
 impl HttpClient {
-    pub(crate) fn new<U: ToSocketAddrs>(toxiproxy_addr: U) -> Self {
+    /// A client with the default settings, failing every request if `base_url` is an error.
+    pub(crate) fn new(base_url: Result<Url, String>) -> Self {
+        Self::with_client(
+            base_url,
+            Client::new(),
+            0,
+            Duration::from_millis(0),
+            AbsentServer::default(),
+        )
+    }
+
+    /// An invalid address doesn't fail here, but every request made by the client does.
+    pub(crate) fn with_client(
+        base_url: Result<Url, String>,
+        client: Client,
+        retries: u32,
+        retry_backoff: Duration,
+        absent_server: AbsentServer,
+    ) -> Self {
         Self {
-            client: Client::new(),
-            toxiproxy_addr: toxiproxy_addr.to_socket_addrs().unwrap().next().unwrap(),
+            client,
+            base_url,
+            retries,
+            retry_backoff,
+            server_version: OnceLock::new(),
+            absent_server,
+            absent: Mutex::new(None),
+            pass_through: PassThroughProxies::default(),
         }
     }
 
+    pub(crate) fn base_url(&self) -> Result<&Url, String> {
+        self.base_url.as_ref().map_err(|err| err.clone())
+    }
+
     pub(crate) fn get(&self, path: &str) -> Result<Response, String> {
-        self.client
-            .get(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .send()
+        let url = self.uri_with_path(path)?;
+        self.send_with_retry(|| self.client.get(url.clone()))
             .map_err(|err| format!("GET error: {}", err))
+            .and_then(|response| check_status("GET", path, response))
     }
 
-    pub(crate) fn post(&self, path: &str) -> Result<Response, String> {
-        self.client
-            .post(self.uri_with_path(path)?)
-            .header("Content-Type", "application/json")
-            .send()
+    /// POST without a body to an endpoint that is safe to repeat, like `reset`.
+    pub(crate) fn post_idempotent(&self, path: &str) -> Result<Response, String> {
+        let url = self.uri_with_path(path)?;
+        self.send_with_retry(|| self.client.post(url.clone()))
             .map_err(|err| format!("POST error: {}", err))
+            .and_then(|response| check_status("POST", path, response))
     }
 
     pub(crate) fn post_with_data(&self, path: &str, body: String) -> Result<Response, String> {
@@ -41,31 +99,217 @@ impl HttpClient {
             .body(body)
             .send()
             .map_err(|err| format!("POST error: {}", err))
+            .and_then(|response| check_status("POST", path, response))
     }
 
-    pub(crate) fn delete(&self, path: &str) -> Result<Response, String> {
+    pub(crate) fn patch_with_data(&self, path: &str, body: String) -> Result<Response, String> {
         self.client
-            .delete(self.uri_with_path(path)?)
+            .patch(self.uri_with_path(path)?)
             .header("Content-Type", "application/json")
+            .body(body)
             .send()
+            .map_err(|err| format!("PATCH error: {}", err))
+            .and_then(|response| check_status("PATCH", path, response))
+    }
+
+    pub(crate) fn delete(&self, path: &str) -> Result<Response, String> {
+        let url = self.uri_with_path(path)?;
+        self.send_with_retry(|| self.client.delete(url.clone()))
             .map_err(|err| format!("DELETE error: {}", err))
+            .and_then(|response| check_status("DELETE", path, response))
     }
 
-    fn uri_with_path(&self, path: &str) -> Result<Url, String> {
-        let mut base: String = "http://".into();
-        base.push_str(&self.toxiproxy_addr.to_string());
+    /// Fetches the version of the server as it reports it, and remembers it for capability
+    /// checks.
+    pub(crate) fn fetch_raw_version(&self) -> Result<String, String> {
+        let body = self
+            .get("version")?
+            .text()
+            .map_err(|err| format!("HTTP response cannot be read: {}", err))?;
+
+        let _ = self.server_version.set(ServerVersion::parse(&body).ok());
+        Ok(body)
+    }
+
+    pub(crate) fn fetch_version(&self) -> Result<ServerVersion, String> {
+        ServerVersion::parse(&self.fetch_raw_version()?)
+    }
+
+    /// Version of the server, fetched once. `None` if the server reports a version that
+    /// cannot be parsed.
+    fn server_version(&self) -> Result<Option<ServerVersion>, String> {
+        if let Some(version) = self.server_version.get() {
+            return Ok(version.clone());
+        }
+
+        self.fetch_raw_version()?;
+        Ok(self.server_version.get().cloned().flatten())
+    }
+
+    /// Whether the server has a feature. A server of unknown version is assumed to have
+    /// everything.
+    pub(crate) fn supports(&self, capability: Capability) -> Result<bool, String> {
+        self.server_version()
+            .map(|version| version.is_none_or(|version| version.supports(capability)))
+    }
+
+    /// Fails if the server is known not to have a feature. Connection errors are left to
+    /// the call needing the feature to report.
+    pub(crate) fn require(&self, capability: Capability) -> Result<(), String> {
+        match self.server_version() {
+            Ok(Some(version)) if !version.supports(capability) => Err(format!(
+                "Toxiproxy server {} does not support {} (requires {} or newer)",
+                version,
+                capability,
+                capability.since()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends a request built by `request`, retrying on connection errors, timeouts and
+    /// gateway errors with an exponential backoff. Only used for idempotent calls.
+    fn send_with_retry<F>(&self, request: F) -> Result<Response, reqwest::Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let result = request().header("Content-Type", "application/json").send();
 
-        let mut url = Url::from_str(&base).map_err(|err| format!("Incorrect address: {}", err))?;
+            let retriable = match &result {
+                Ok(response) => matches!(
+                    response.status(),
+                    StatusCode::BAD_GATEWAY
+                        | StatusCode::SERVICE_UNAVAILABLE
+                        | StatusCode::GATEWAY_TIMEOUT
+                ),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
 
-        url.set_scheme("http")
-            .map_err(|_| "invalid scheme".to_owned())?;
-        url.set_path(path);
-        Ok(url)
+            if !retriable || attempt >= self.retries {
+                return result;
+            }
+
+            thread::sleep(
+                self.retry_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt)),
+            );
+            attempt += 1;
+        }
     }
 
+    pub(crate) fn absent_server(&self) -> AbsentServer {
+        self.absent_server
+    }
+
+    /// Whether the policy applies: the server is not running and the policy is not
+    /// `AbsentServer::Fail`, which never checks.
+    pub(crate) fn is_absent(&self) -> bool {
+        if self.absent_server == AbsentServer::Fail {
+            return false;
+        }
+
+        let mut absent = self.absent.lock().unwrap_or_else(PoisonError::into_inner);
+        match *absent {
+            Some((checked_at, cached)) if checked_at.elapsed() < ABSENT_CHECK_TTL => cached,
+            _ => {
+                let is_absent = !self.is_alive();
+                *absent = Some((Instant::now(), is_absent));
+                is_absent
+            }
+        }
+    }
+
+    /// Whether proxies stand in for the server's, passing traffic through untouched.
+    pub(crate) fn passes_through(&self) -> bool {
+        self.absent_server == AbsentServer::PassThrough && self.is_absent()
+    }
+
+    fn uri_with_path(&self, path: &str) -> Result<Url, String> {
+        if self.is_absent() {
+            return Err(format!(
+                "Toxiproxy server at {} is not running (absent server policy: {})",
+                self.base_url()?,
+                self.absent_server
+            ));
+        }
+
+        self.base_url()?
+            .join(path)
+            .map_err(|err| format!("Incorrect path `{}`: {}", path, err))
+    }
+
+    /// Connects to the API port, resolving the host on every call.
     pub(crate) fn is_alive(&self) -> bool {
-        std::net::TcpStream::connect(self.toxiproxy_addr)
-            .map(|_| true)
+        self.base_url()
+            .ok()
+            .and_then(|url| Some((url.host_str()?.to_owned(), url.port_or_known_default()?)))
+            .map(|(host, port)| connect(&format!("{}:{}", host, port), ALIVE_TIMEOUT).is_ok())
             .unwrap_or(false)
     }
 }
+
+/// Turns error responses into an error carrying the server's message.
+fn check_status(method: &str, path: &str, response: Response) -> Result<Response, String> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().unwrap_or_default();
+    let message = serde_json::from_str::<ErrorBody>(&body)
+        .map(|error_body| error_body.error)
+        .unwrap_or(body);
+
+    Err(format!(
+        "{} {} failed with status {}: {}",
+        method,
+        path,
+        status,
+        message.trim()
+    ))
+}
+
+/// Parses the server address: either `host:port` (`host` defaults to port 8474) or a full URL
+/// with a scheme and an optional path prefix, like `https://proxy.local/toxiproxy`.
+/// Host names are not resolved here, only when a request is made.
+pub(crate) fn parse_base_url(toxiproxy_addr: &str) -> Result<Url, String> {
+    let toxiproxy_addr = toxiproxy_addr.trim();
+    let has_scheme = toxiproxy_addr.contains("://");
+    let address = if has_scheme {
+        toxiproxy_addr.to_owned()
+    } else {
+        format!("http://{}", toxiproxy_addr)
+    };
+
+    let mut url = Url::parse(&address)
+        .map_err(|err| format!("Incorrect address `{}`: {}", toxiproxy_addr, err))?;
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(format!(
+            "Incorrect address `{}`: unsupported scheme `{}`",
+            toxiproxy_addr,
+            url.scheme()
+        ));
+    }
+    if !matches!(url.host_str(), Some(host) if !host.is_empty()) {
+        return Err(format!(
+            "Incorrect address `{}`: missing host",
+            toxiproxy_addr
+        ));
+    }
+    if !has_scheme && url.port().is_none() {
+        url.set_port(Some(DEFAULT_PORT))
+            .map_err(|_| format!("Incorrect address `{}`", toxiproxy_addr))?;
+    }
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url.set_query(None);
+    url.set_fragment(None);
+
+    Ok(url)
+}
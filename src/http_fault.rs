@@ -0,0 +1,466 @@
+//! HTTP-level faults for services built on tower and hyper, enabled by the `tower` feature:
+//! what TCP toxics cannot express, like answering 503 to some of the requests or delaying the
+//! response headers. Services use the `http` 1 and `http-body` 1 types, as in hyper 1 and
+//! axum 0.7 or later.
+//!
+//! An [`HttpFault`] is configured like a `ToxicPack`: a type, a stream, a toxicity and
+//! attributes. `Stream::Upstream` applies it to the request, before the inner service is
+//! called, and `Stream::Downstream` to the response. The toxicity is the probability of a
+//! fault applying to each request.
+//!
+//! - `latency`: delays the request or the response headers by `latency` milliseconds, give
+//!   or take `jitter`
+//! - `status`: answers with the `status` code and an optional `body` string instead of the
+//!   inner service (upstream), or in place of its response (downstream)
+//! - `abort`: fails the response body after `bytes` bytes (downstream only)
+//! - `bandwidth`: sends the response body at `rate` KB/s (downstream only)
+//!
+//! Delays are measured on a [`Clock`], real time unless [`HttpFaultLayer::clock`] sets
+//! another one, like a `TokioClock` for tests under `tokio::time::pause`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! use toxiproxy_rust::http_fault::{HttpFault, HttpFaultLayer};
+//! use toxiproxy_rust::toxic::{Stream, Toxicity};
+//!
+//! let mut attributes = HashMap::new();
+//! attributes.insert("status".into(), 503.into());
+//! let unavailable = HttpFault::new("status".into(), Stream::Upstream, Toxicity::percent(10), attributes);
+//!
+//! let layer = HttpFaultLayer::new(vec![unavailable]).expect("faults are valid");
+//! // let service = tower::ServiceBuilder::new().layer(layer).service(my_service);
+//! ```
+//!
+//! [`HttpFault`]: struct.HttpFault.html
+//! [`Clock`]: ../clock/trait.Clock.html
+//! [`HttpFaultLayer::clock`]: struct.HttpFaultLayer.html#method.clock
+
+use super::clock::*;
+use super::rng::*;
+use super::toxic::*;
+use bytes::{Buf, Bytes};
+use http::{Request, Response, StatusCode};
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Error of a response body going through [`HttpFaultService`].
+///
+/// [`HttpFaultService`]: struct.HttpFaultService.html
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+const ABORTED: &str = "response body aborted by an HTTP fault";
+
+/// Config of an HTTP fault.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpFault {
+    pub name: String,
+    pub r#type: String,
+    pub stream: Stream,
+    pub toxicity: Toxicity,
+    pub attributes: ToxicAttributes,
+}
+
+impl HttpFault {
+    /// Creates an HTTP fault config, named after its type and stream like toxics are.
+    pub fn new(
+        r#type: String,
+        stream: Stream,
+        toxicity: Toxicity,
+        attributes: ToxicAttributes,
+    ) -> Self {
+        Self {
+            name: format!("{}_{}", r#type, stream),
+            r#type,
+            stream,
+            toxicity,
+            attributes,
+        }
+    }
+
+    /// Checks the type, the stream and the attributes of the fault.
+    pub fn validate(&self) -> Result<(), String> {
+        let invalid = |reason: String| format!("invalid HTTP fault `{}`: {}", self.name, reason);
+
+        let (attributes, required): (&[&str], &[&str]) = match self.r#type.as_str() {
+            "latency" => (&["latency", "jitter"], &[]),
+            "status" => (&["status", "body"], &["status"]),
+            "abort" => (&["bytes"], &["bytes"]),
+            "bandwidth" => (&["rate"], &["rate"]),
+            other => {
+                return Err(invalid(format!(
+                    "unknown type `{}`, expected `latency`, `status`, `abort` or `bandwidth`",
+                    other
+                )))
+            }
+        };
+        if let Some(key) = self
+            .attributes
+            .keys()
+            .find(|key| !attributes.contains(&key.as_str()))
+        {
+            return Err(invalid(format!(
+                "unknown attribute `{}` for type `{}`",
+                key, self.r#type
+            )));
+        }
+        if let Some(key) = required
+            .iter()
+            .find(|key| !self.attributes.contains_key(**key))
+        {
+            return Err(invalid(format!("missing attribute `{}`", key)));
+        }
+
+        for (key, value) in &self.attributes {
+            let valid = match key.as_str() {
+                "body" => value.is_string(),
+                "status" => value
+                    .as_u64()
+                    .and_then(|status| u16::try_from(status).ok())
+                    .is_some_and(|status| StatusCode::from_u16(status).is_ok()),
+                "rate" => value.as_u64().is_some_and(|rate| rate > 0),
+                _ => value.is_u64(),
+            };
+            if !valid {
+                return Err(invalid(format!("attribute `{}` cannot be {}", key, value)));
+            }
+        }
+
+        match (self.r#type.as_str(), self.stream) {
+            ("abort", Stream::Downstream) | ("bandwidth", Stream::Downstream) => Ok(()),
+            ("abort", _) | ("bandwidth", _) => Err(invalid(
+                "only applies to responses, with `Stream::Downstream`".into(),
+            )),
+            ("status", Stream::Both) => Err(invalid(
+                "applies either to requests or to responses, not both".into(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn attribute(&self, name: &str) -> u64 {
+        self.attributes
+            .get(name)
+            .and_then(Value::as_u64)
+            .unwrap_or_default()
+    }
+
+    fn applies_to(&self, stream: Stream) -> bool {
+        self.stream == stream || self.stream == Stream::Both
+    }
+}
+
+/// Layer adding [`HttpFault`]s to a service.
+///
+/// [`HttpFault`]: struct.HttpFault.html
+#[derive(Debug, Clone)]
+pub struct HttpFaultLayer {
+    faults: Arc<Vec<HttpFault>>,
+    clock: Arc<dyn Clock>,
+    seed: Option<u64>,
+}
+
+impl HttpFaultLayer {
+    /// Creates the layer, failing if a fault is invalid.
+    pub fn new(faults: Vec<HttpFault>) -> Result<Self, String> {
+        faults.iter().try_for_each(HttpFault::validate)?;
+
+        Ok(Self {
+            faults: Arc::new(faults),
+            clock: Arc::new(SystemClock),
+            seed: None,
+        })
+    }
+
+    /// Clock measuring the delays, `SystemClock` by default.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Seed of the random draws (toxicity, jitter) of each service created by the layer, for
+    /// the same faults on the same requests on every run. Random by default.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+impl<S> Layer<S> for HttpFaultLayer {
+    type Service = HttpFaultService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpFaultService {
+            inner,
+            faults: self.faults.clone(),
+            clock: self.clock.clone(),
+            rng: Arc::new(Mutex::new(self.seed.map_or_else(Rng::new, Rng::from_seed))),
+        }
+    }
+}
+
+/// Service applying [`HttpFault`]s around an inner service, created by [`HttpFaultLayer`].
+///
+/// [`HttpFault`]: struct.HttpFault.html
+/// [`HttpFaultLayer`]: struct.HttpFaultLayer.html
+#[derive(Debug, Clone)]
+pub struct HttpFaultService<S> {
+    inner: S,
+    faults: Arc<Vec<HttpFault>>,
+    clock: Arc<dyn Clock>,
+    rng: Arc<Mutex<Rng>>,
+}
+
+/// Faults drawn for a request.
+#[derive(Debug, Default)]
+struct Plan {
+    request_delay: Duration,
+    request_status: Option<(StatusCode, Bytes)>,
+    response_delay: Duration,
+    response_status: Option<(StatusCode, Bytes)>,
+    abort_after: Option<u64>,
+    rate: Option<u64>,
+}
+
+impl<S> HttpFaultService<S> {
+    fn plan(&self) -> Plan {
+        let mut rng = self.rng.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut plan = Plan::default();
+
+        for fault in self.faults.iter() {
+            if rng.next_f32() >= fault.toxicity.value() {
+                continue;
+            }
+
+            match fault.r#type.as_str() {
+                "latency" => {
+                    let (latency, jitter) = (fault.attribute("latency"), fault.attribute("jitter"));
+                    let mut delay = || Duration::from_millis(rng.around(latency, jitter));
+                    if fault.applies_to(Stream::Upstream) {
+                        plan.request_delay += delay();
+                    }
+                    if fault.applies_to(Stream::Downstream) {
+                        plan.response_delay += delay();
+                    }
+                }
+                "status" => {
+                    let status = StatusCode::from_u16(fault.attribute("status") as u16)
+                        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                    let body = fault
+                        .attributes
+                        .get("body")
+                        .and_then(Value::as_str)
+                        .map(|body| Bytes::from(body.to_owned()))
+                        .unwrap_or_default();
+                    if fault.stream == Stream::Upstream {
+                        plan.request_status.get_or_insert((status, body));
+                    } else {
+                        plan.response_status.get_or_insert((status, body));
+                    }
+                }
+                "abort" => plan.abort_after = Some(fault.attribute("bytes")),
+                "bandwidth" => plan.rate = Some(fault.attribute("rate")),
+                _ => {}
+            }
+        }
+
+        plan
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpFaultService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+{
+    type Response = Response<FaultBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let plan = self.plan();
+        // The service that was polled ready handles the request, the clone takes its place.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let clock = self.clock.clone();
+
+        Box::pin(async move {
+            delay(clock.as_ref(), plan.request_delay).await;
+            if let Some((status, body)) = plan.request_status {
+                return Ok(injected_response(status, body, clock));
+            }
+
+            let response = inner.call(request).await?;
+            delay(clock.as_ref(), plan.response_delay).await;
+            if let Some((status, body)) = plan.response_status {
+                return Ok(injected_response(status, body, clock));
+            }
+
+            Ok(response.map(|body| {
+                let mut body = FaultBody::new(Some(body), clock);
+                body.abort_after = plan.abort_after;
+                body.rate = plan.rate;
+                body
+            }))
+        })
+    }
+}
+
+async fn delay(clock: &dyn Clock, duration: Duration) {
+    if duration.is_zero() {
+        return;
+    }
+
+    if let Some(timer) = clock.timer(clock.now() + duration) {
+        timer.await;
+    }
+}
+
+fn injected_response<B>(
+    status: StatusCode,
+    body: Bytes,
+    clock: Arc<dyn Clock>,
+) -> Response<FaultBody<B>> {
+    let mut fault_body = FaultBody::new(None, clock);
+    fault_body.injected = Some(body);
+
+    let mut response = Response::new(fault_body);
+    *response.status_mut() = status;
+    response
+}
+
+pin_project! {
+    /// Body of the responses of [`HttpFaultService`]: the inner service's body with the
+    /// `abort` and `bandwidth` faults applied, or the body of an injected `status`.
+    ///
+    /// [`HttpFaultService`]: struct.HttpFaultService.html
+    #[derive(Debug)]
+    pub struct FaultBody<B> {
+        #[pin]
+        inner: Option<B>,
+        injected: Option<Bytes>,
+        abort_after: Option<u64>,
+        rate: Option<u64>,
+        sent: u64,
+        // A chunk held back until the bandwidth allows it.
+        held: Option<(Bytes, Instant)>,
+        clock: Arc<dyn Clock>,
+        alarm: Alarm,
+        aborted: bool,
+    }
+}
+
+impl<B> FaultBody<B> {
+    fn new(inner: Option<B>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner,
+            injected: None,
+            abort_after: None,
+            rate: None,
+            sent: 0,
+            held: None,
+            clock,
+            alarm: Alarm::default(),
+            aborted: false,
+        }
+    }
+}
+
+impl<B> Body for FaultBody<B>
+where
+    B: Body,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+
+        if let Some((_, ready_at)) = this.held {
+            ready!(this.alarm.poll_until(this.clock.as_ref(), *ready_at, cx));
+            return Poll::Ready(this.held.take().map(|(held, _)| Ok(Frame::data(held))));
+        }
+        if let Some(injected) = this.injected.take() {
+            return Poll::Ready(Some(Ok(Frame::data(injected))));
+        }
+        if *this.aborted {
+            return Poll::Ready(Some(Err(ABORTED.into())));
+        }
+
+        let inner = match this.inner.as_mut().as_pin_mut() {
+            Some(inner) => inner,
+            None => return Poll::Ready(None),
+        };
+        let frame = match ready!(inner.poll_frame(cx)) {
+            Some(Ok(frame)) => frame.map_data(|mut data| data.copy_to_bytes(data.remaining())),
+            Some(Err(err)) => return Poll::Ready(Some(Err(err.into()))),
+            None => return Poll::Ready(None),
+        };
+        // Trailers go through as they are.
+        let mut chunk = match frame.into_data() {
+            Ok(chunk) => chunk,
+            Err(frame) => return Poll::Ready(Some(Ok(frame))),
+        };
+
+        if let Some(limit) = *this.abort_after {
+            let remaining = limit.saturating_sub(*this.sent);
+            if chunk.len() as u64 > remaining {
+                // The body fails once the part within the limit is sent.
+                *this.aborted = true;
+                if remaining == 0 {
+                    return Poll::Ready(Some(Err(ABORTED.into())));
+                }
+                chunk.truncate(remaining as usize);
+            }
+        }
+        *this.sent += chunk.len() as u64;
+
+        if let Some(rate) = *this.rate {
+            let ready_at =
+                this.clock.now() + Duration::from_micros(chunk.len() as u64 * 1000 / rate);
+            *this.held = Some((chunk, ready_at));
+            ready!(this.alarm.poll_until(this.clock.as_ref(), ready_at, cx));
+            return Poll::Ready(this.held.take().map(|(held, _)| Ok(Frame::data(held))));
+        }
+
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.held.is_none()
+            && self.injected.is_none()
+            && !self.aborted
+            && self.inner.as_ref().is_none_or(Body::is_end_stream)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match (&self.injected, &self.inner) {
+            (Some(injected), _) => SizeHint::with_exact(injected.len() as u64),
+            (None, Some(inner)) if self.abort_after.is_none() => inner.size_hint(),
+            (None, Some(_)) => SizeHint::default(),
+            (None, None) => SizeHint::with_exact(0),
+        }
+    }
+}
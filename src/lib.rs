@@ -27,7 +27,7 @@
 //! ## Setting up a more advanced test
 //!
 //! ```rust
-//! use toxiproxy_rust::{TOXIPROXY, proxy::ProxyPack};
+//! use toxiproxy_rust::{TOXIPROXY, proxy::ProxyPack, toxic::{Stream, Toxicity}};
 //!
 //! TOXIPROXY.populate(vec![ProxyPack::new(
 //!     "socket".into(),
@@ -38,8 +38,8 @@
 //! TOXIPROXY
 //!     .find_and_reset_proxy("socket")
 //!     .unwrap()
-//!     .with_slicer("downstream".into(), 2048, 128, 0, 0.8)
-//!     .with_bandwidth("downstream".into(), 32, 0.5)
+//!     .with_slicer(Stream::Downstream, 2048, 128, 0, Toxicity::percent(80))
+//!     .with_bandwidth(Stream::Both, 32, Toxicity::percent(50))
 //!     .apply(|| {
 //!         /* For example:
 //!         let result = MyService::Server.call();
@@ -53,15 +53,45 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod absent_server;
+pub mod batch;
 pub mod client;
+pub mod clock;
 mod consts;
+pub mod fault_matrix;
+pub mod fixture;
 mod http_client;
+#[cfg(feature = "tower")]
+pub mod http_fault;
+pub mod metrics;
+pub mod probe;
 pub mod proxy;
+mod rng;
+pub mod service_url;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod testing;
+pub mod topology;
 pub mod toxic;
+pub mod toxic_stream;
+pub mod version;
+mod wait;
 
 use client::*;
 
+#[cfg(feature = "macros")]
+pub use toxiproxy_rust_macros::toxiproxy_test;
+
 lazy_static! {
-    /// Pre-built client using the default connection address.
-    pub static ref TOXIPROXY: Client = Client::new("127.0.0.1:8474");
+    /// Pre-built client using the default connection address, or the one configured by the
+    /// `TOXIPROXY_URL` or `TOXIPROXY_HOST` / `TOXIPROXY_PORT` environment variables.
+    /// See [`Client::from_env`] for all the variables.
+    ///
+    /// # Panics
+    ///
+    /// Panics on first use if the environment variables are invalid.
+    ///
+    /// [`Client::from_env`]: client/struct.Client.html#method.from_env
+    pub static ref TOXIPROXY: Client =
+        Client::from_env().expect("Toxiproxy client cannot be configured from the environment");
 }
@@ -0,0 +1,236 @@
+//! Traffic counters of the proxies, read from the server's Prometheus [`/metrics`] endpoint
+//! (Toxiproxy 2.6 and newer).
+//!
+//! [`/metrics`]: https://github.com/Shopify/toxiproxy#metrics
+
+use super::http_client::*;
+use super::toxic::Stream;
+use super::version::*;
+use std::collections::HashMap;
+
+/// Prefix of the per-proxy metrics.
+const PROXY_METRIC_PREFIX: &str = "toxiproxy_proxy_";
+
+/// A single sample of the Prometheus text format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub value: f64,
+}
+
+/// Counters of one direction of a proxy.
+///
+/// Counters other than the byte counts (for example connection counts, depending on the
+/// server version) are kept in `counters`, keyed by the metric name without the
+/// `toxiproxy_proxy_` prefix and the `_total` suffix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamStats {
+    /// Bytes read by the proxy from the sending side.
+    pub received_bytes: u64,
+    /// Bytes written by the proxy to the receiving side, after toxics were applied.
+    pub sent_bytes: u64,
+    pub counters: HashMap<String, f64>,
+}
+
+impl StreamStats {
+    /// Value of a counter, by its short name (`connections`) or full metric name.
+    pub fn counter(&self, name: &str) -> Option<f64> {
+        let name = short_name(name);
+        match name {
+            "received_bytes" => Some(self.received_bytes as f64),
+            "sent_bytes" => Some(self.sent_bytes as f64),
+            _ => self.counters.get(name).copied(),
+        }
+    }
+}
+
+/// Counters of a proxy, per direction. A proxy that has not seen traffic yet has all
+/// counters at zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProxyStats {
+    /// Data sent from the client towards the upstream service.
+    pub upstream: StreamStats,
+    /// Data sent from the upstream service back to the client.
+    pub downstream: StreamStats,
+}
+
+impl ProxyStats {
+    /// Counters of one direction, `Stream::Both` sums up the two.
+    pub fn stream(&self, stream: Stream) -> StreamStats {
+        match stream {
+            Stream::Upstream => self.upstream.clone(),
+            Stream::Downstream => self.downstream.clone(),
+            Stream::Both => {
+                let mut both = self.upstream.clone();
+                both.received_bytes += self.downstream.received_bytes;
+                both.sent_bytes += self.downstream.sent_bytes;
+                for (name, value) in &self.downstream.counters {
+                    *both.counters.entry(name.clone()).or_insert(0.0) += value;
+                }
+                both
+            }
+        }
+    }
+
+    fn stream_mut(&mut self, direction: &str) -> Option<&mut StreamStats> {
+        match direction {
+            "upstream" => Some(&mut self.upstream),
+            "downstream" => Some(&mut self.downstream),
+            _ => None,
+        }
+    }
+}
+
+/// Metrics reported by the server.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    samples: Vec<Sample>,
+    proxies: HashMap<String, ProxyStats>,
+}
+
+impl Metrics {
+    /// Parses metrics in the Prometheus text format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::metrics::Metrics;
+    /// let metrics = Metrics::parse(
+    ///     r#"# TYPE toxiproxy_proxy_sent_bytes_total counter
+    /// toxiproxy_proxy_sent_bytes_total{direction="downstream",proxy="redis"} 42
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(42, metrics.proxy("redis").downstream.sent_bytes);
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let samples = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(parse_sample)
+            .collect::<Result<Vec<Sample>, String>>()?;
+
+        let mut proxies: HashMap<String, ProxyStats> = HashMap::new();
+        for sample in &samples {
+            if !sample.name.starts_with(PROXY_METRIC_PREFIX) {
+                continue;
+            }
+            let (proxy, direction) =
+                match (sample.labels.get("proxy"), sample.labels.get("direction")) {
+                    (Some(proxy), Some(direction)) => (proxy, direction),
+                    _ => continue,
+                };
+            let stats = match proxies
+                .entry(proxy.clone())
+                .or_default()
+                .stream_mut(direction)
+            {
+                Some(stats) => stats,
+                None => continue,
+            };
+
+            match short_name(&sample.name) {
+                "received_bytes" => stats.received_bytes += sample.value as u64,
+                "sent_bytes" => stats.sent_bytes += sample.value as u64,
+                name => *stats.counters.entry(name.to_owned()).or_insert(0.0) += sample.value,
+            }
+        }
+
+        Ok(Self { samples, proxies })
+    }
+
+    /// Counters of a proxy, zero if the server has not reported any for it.
+    pub fn proxy(&self, name: &str) -> ProxyStats {
+        self.proxies.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Counters of all proxies that have seen traffic, by proxy name.
+    pub fn proxies(&self) -> &HashMap<String, ProxyStats> {
+        &self.proxies
+    }
+
+    /// All samples, including the server's own runtime metrics.
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub(crate) fn fetch(client: &HttpClient) -> Result<Self, String> {
+        client.require(Capability::Metrics)?;
+
+        client
+            .get("metrics")?
+            .text()
+            .map_err(|err| format!("HTTP response cannot be read: {}", err))
+            .and_then(|text| Self::parse(&text))
+    }
+}
+
+/// Name of a proxy counter without the common prefix and the `_total` suffix.
+fn short_name(name: &str) -> &str {
+    let name = name.strip_prefix(PROXY_METRIC_PREFIX).unwrap_or(name);
+    name.strip_suffix("_total").unwrap_or(name)
+}
+
+/// Parses a `name{label="value",...} value [timestamp]` line.
+fn parse_sample(line: &str) -> Result<Sample, String> {
+    let invalid = |reason: &str| format!("invalid metrics line `{}`: {}", line, reason);
+
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or_else(|| invalid("missing value"))?;
+    let name = &line[..name_end];
+    let mut rest = &line[name_end..];
+    let mut labels = HashMap::new();
+
+    if let Some(label_text) = rest.strip_prefix('{') {
+        rest = label_text;
+        loop {
+            rest = rest.trim_start_matches([',', ' ']);
+            if let Some(after) = rest.strip_prefix('}') {
+                rest = after;
+                break;
+            }
+
+            let (key, after_key) = rest.split_once("=\"").ok_or_else(|| invalid("bad label"))?;
+            let (value, after_value) =
+                parse_label_value(after_key).ok_or_else(|| invalid("unterminated label"))?;
+            labels.insert(key.trim().to_owned(), value);
+            rest = after_value;
+        }
+    }
+
+    let value = rest
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| invalid("missing value"))?;
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| invalid("value is not a number"))?;
+
+    Ok(Sample {
+        name: name.to_owned(),
+        labels,
+        value,
+    })
+}
+
+/// Reads an escaped label value up to its closing quote, returns it and the text after it.
+fn parse_label_value(text: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[index + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+
+    None
+}
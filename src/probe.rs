@@ -0,0 +1,377 @@
+//! Measures how connections through a [`Proxy`] behave, to check that toxics take effect
+//! without hand-written socket code.
+//!
+//! Round trips and throughput expect an echo service behind the proxy (one that writes back
+//! everything it reads). Connect time and close behaviour work with any upstream.
+//!
+//! The `assert_*` helpers panic with a message naming the proxy and the measured value.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use std::time::Duration;
+//! # use toxiproxy_rust::{probe, toxic::{Stream, Toxicity}};
+//! let proxy = toxiproxy_rust::TOXIPROXY.find_and_reset_proxy("echo").unwrap();
+//! proxy
+//!     .with_latency(Stream::Downstream, 1000, 0, Toxicity::always())
+//!     .apply(|| {
+//!         probe::assert_latency_between(&proxy, Duration::from_millis(900), Duration::from_millis(1200));
+//!     });
+//! ```
+//!
+//! [`Proxy`]: ../proxy/struct.Proxy.html
+
+use super::proxy::*;
+use super::wait::*;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Payload of the round trips made by the latency assertions.
+const PING: &[u8] = b"ping";
+
+/// Extra time given to an operation on top of the upper bound being asserted, so a value just
+/// above the bound is reported as such rather than as a timeout.
+const ASSERT_SLACK: Duration = Duration::from_secs(1);
+
+/// How a connection through the proxy ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseBehaviour {
+    /// The proxy refused the connection, for example because it is disabled.
+    Refused,
+    /// The connection was closed cleanly after the given time.
+    Closed(Duration),
+    /// The connection was reset after the given time.
+    Reset(Duration),
+    /// The connection was still open when the probe gave up.
+    Open,
+}
+
+/// Result of a throughput measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    /// Bytes that made the round trip.
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl Throughput {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Time to open a connection through the proxy.
+pub fn connect_time(proxy: &Proxy, timeout: Duration) -> Result<Duration, String> {
+    let start = Instant::now();
+    open(proxy, timeout).map(|_| start.elapsed())
+}
+
+/// Time for `payload` to go through the proxy to an echo upstream and back, on a fresh
+/// connection (the connect time is not included).
+pub fn round_trip(proxy: &Proxy, payload: &[u8], timeout: Duration) -> Result<Duration, String> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = open(proxy, timeout)?;
+
+    let start = Instant::now();
+    stream
+        .write_all(payload)
+        .map_err(|err| probe_error(proxy, "sending failed", err))?;
+    read_exact_until(proxy, &mut stream, payload.len(), deadline)?;
+
+    Ok(start.elapsed())
+}
+
+/// Sends `bytes` bytes through the proxy to an echo upstream, and measures how fast they come
+/// back.
+pub fn throughput(proxy: &Proxy, bytes: usize, timeout: Duration) -> Result<Throughput, String> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = open(proxy, timeout)?;
+    let mut writer = stream
+        .try_clone()
+        .map_err(|err| probe_error(proxy, "cannot clone connection", err))?;
+
+    let start = Instant::now();
+    let sender = thread::spawn(move || writer.write_all(&vec![0u8; bytes]));
+    let received = read_exact_until(proxy, &mut stream, bytes, deadline);
+    let _ = stream.shutdown(Shutdown::Both);
+
+    sender
+        .join()
+        .map_err(|_| format!("probe of proxy `{}`: sender panicked", proxy.name()))?
+        .map_err(|err| probe_error(proxy, "sending failed", err))?;
+    received?;
+
+    Ok(Throughput {
+        bytes,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Opens a connection through the proxy, sends a byte and reads until the connection ends
+/// or `timeout` elapses.
+pub fn close_behaviour(proxy: &Proxy, timeout: Duration) -> Result<CloseBehaviour, String> {
+    let deadline = Instant::now() + timeout;
+    let mut stream = match open(proxy, timeout) {
+        Ok(stream) => stream,
+        Err(_) if is_refused(proxy, timeout) => return Ok(CloseBehaviour::Refused),
+        Err(err) => return Err(err),
+    };
+
+    let start = Instant::now();
+    if let Err(err) = stream.write_all(PING) {
+        return classify(proxy, err, start.elapsed());
+    }
+
+    let mut buf = [0u8; 1024];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(CloseBehaviour::Open);
+        }
+        stream
+            .set_read_timeout(Some(remaining))
+            .map_err(|err| probe_error(proxy, "cannot set timeout", err))?;
+
+        match stream.read(&mut buf) {
+            Ok(0) => return Ok(CloseBehaviour::Closed(start.elapsed())),
+            Ok(_) => continue,
+            Err(err) if is_timeout(&err) => return Ok(CloseBehaviour::Open),
+            Err(err) => return classify(proxy, err, start.elapsed()),
+        }
+    }
+}
+
+/// Asserts the round trip through the proxy to an echo upstream takes between `min` and `max`.
+///
+/// # Panics
+///
+/// Panics if the round trip is outside the range or fails.
+#[track_caller]
+pub fn assert_latency_between(proxy: &Proxy, min: Duration, max: Duration) {
+    let latency =
+        round_trip(proxy, PING, max + ASSERT_SLACK).unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(
+        min <= latency && latency <= max,
+        "round trip through proxy `{}` took {:?}, expected between {:?} and {:?}",
+        proxy.name(),
+        latency,
+        min,
+        max
+    );
+}
+
+/// Asserts a connection through the proxy opens within `max`.
+///
+/// # Panics
+///
+/// Panics if connecting takes longer or fails.
+#[track_caller]
+pub fn assert_connect_time_below(proxy: &Proxy, max: Duration) {
+    let elapsed = connect_time(proxy, max + ASSERT_SLACK).unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(
+        elapsed <= max,
+        "connecting through proxy `{}` took {:?}, expected at most {:?}",
+        proxy.name(),
+        elapsed,
+        max
+    );
+}
+
+/// Asserts `bytes` bytes make the round trip to an echo upstream at a rate between
+/// `min_bytes_per_sec` and `max_bytes_per_sec`, within `timeout`.
+///
+/// # Panics
+///
+/// Panics if the rate is outside the range or the transfer fails.
+#[track_caller]
+pub fn assert_throughput_between(
+    proxy: &Proxy,
+    bytes: usize,
+    min_bytes_per_sec: f64,
+    max_bytes_per_sec: f64,
+    timeout: Duration,
+) {
+    let rate = throughput(proxy, bytes, timeout)
+        .unwrap_or_else(|err| panic!("{}", err))
+        .bytes_per_sec();
+
+    assert!(
+        min_bytes_per_sec <= rate && rate <= max_bytes_per_sec,
+        "throughput through proxy `{}` was {:.0} bytes/s, expected between {:.0} and {:.0}",
+        proxy.name(),
+        rate,
+        min_bytes_per_sec,
+        max_bytes_per_sec
+    );
+}
+
+/// Asserts the proxy refuses connections.
+///
+/// # Panics
+///
+/// Panics if a connection can be opened.
+#[track_caller]
+pub fn assert_refused(proxy: &Proxy) {
+    assert_close_behaviour(
+        proxy,
+        ASSERT_SLACK,
+        |behaviour| behaviour == CloseBehaviour::Refused,
+        "to be refused",
+    );
+}
+
+/// Asserts a connection through the proxy is closed cleanly within `max`.
+///
+/// # Panics
+///
+/// Panics if the connection stays open longer, is reset or refused.
+#[track_caller]
+pub fn assert_closed_within(proxy: &Proxy, max: Duration) {
+    assert_close_behaviour(
+        proxy,
+        max,
+        |behaviour| matches!(behaviour, CloseBehaviour::Closed(elapsed) if elapsed <= max),
+        "to be closed",
+    );
+}
+
+/// Asserts a connection through the proxy is reset within `max`.
+///
+/// # Panics
+///
+/// Panics if the connection stays open longer, is closed cleanly or refused.
+#[track_caller]
+pub fn assert_reset_within(proxy: &Proxy, max: Duration) {
+    assert_close_behaviour(
+        proxy,
+        max,
+        |behaviour| matches!(behaviour, CloseBehaviour::Reset(elapsed) if elapsed <= max),
+        "to be reset",
+    );
+}
+
+/// Asserts a connection through the proxy stays open for at least `duration`.
+///
+/// # Panics
+///
+/// Panics if the connection ends earlier or is refused.
+#[track_caller]
+pub fn assert_stays_open(proxy: &Proxy, duration: Duration) {
+    assert_close_behaviour(
+        proxy,
+        duration,
+        |behaviour| behaviour == CloseBehaviour::Open,
+        "to stay open",
+    );
+}
+
+#[track_caller]
+fn assert_close_behaviour<F>(proxy: &Proxy, timeout: Duration, expected: F, description: &str)
+where
+    F: Fn(CloseBehaviour) -> bool,
+{
+    let behaviour = close_behaviour(proxy, timeout).unwrap_or_else(|err| panic!("{}", err));
+
+    assert!(
+        expected(behaviour),
+        "expected connection through proxy `{}` {} within {:?}, got {:?}",
+        proxy.name(),
+        description,
+        timeout,
+        behaviour
+    );
+}
+
+fn open(proxy: &Proxy, timeout: Duration) -> Result<TcpStream, String> {
+    let stream = connect(&proxy.connect_addr()?, timeout)
+        .map_err(|err| format!("probe of proxy `{}`: {}", proxy.name(), err))?;
+    stream
+        .set_nodelay(true)
+        .map_err(|err| probe_error(proxy, "cannot configure connection", err))?;
+
+    Ok(stream)
+}
+
+/// Whether a new connection to the proxy is actively refused (rather than timing out).
+fn is_refused(proxy: &Proxy, timeout: Duration) -> bool {
+    proxy
+        .connect_addr()
+        .ok()
+        .and_then(|addr| std::net::ToSocketAddrs::to_socket_addrs(&addr).ok())
+        .map(|mut addrs| {
+            addrs.all(|addr| {
+                matches!(
+                    TcpStream::connect_timeout(&addr, timeout),
+                    Err(err) if err.kind() == io::ErrorKind::ConnectionRefused
+                )
+            })
+        })
+        .unwrap_or(false)
+}
+
+fn read_exact_until(
+    proxy: &Proxy,
+    stream: &mut TcpStream,
+    len: usize,
+    deadline: Instant,
+) -> Result<(), String> {
+    let mut buf = vec![0u8; len.clamp(1, 64 * 1024)];
+    let mut read = 0;
+
+    while read < len {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!(
+                "probe of proxy `{}`: timed out after receiving {} of {} bytes",
+                proxy.name(),
+                read,
+                len
+            ));
+        }
+        stream
+            .set_read_timeout(Some(remaining))
+            .map_err(|err| probe_error(proxy, "cannot set timeout", err))?;
+
+        let chunk = buf.len().min(len - read);
+        match stream.read(&mut buf[..chunk]) {
+            Ok(0) => {
+                return Err(format!(
+                    "probe of proxy `{}`: connection closed after receiving {} of {} bytes",
+                    proxy.name(),
+                    read,
+                    len
+                ))
+            }
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) if is_timeout(&err) => continue,
+            Err(err) => return Err(probe_error(proxy, "receiving failed", err)),
+        }
+    }
+
+    Ok(())
+}
+
+fn classify(proxy: &Proxy, err: io::Error, elapsed: Duration) -> Result<CloseBehaviour, String> {
+    match err.kind() {
+        io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::BrokenPipe => Ok(CloseBehaviour::Reset(elapsed)),
+        _ => Err(probe_error(proxy, "connection failed", err)),
+    }
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+fn probe_error(proxy: &Proxy, what: &str, err: io::Error) -> String {
+    format!("probe of proxy `{}`: {}: {}", proxy.name(), what, err)
+}
@@ -6,13 +6,18 @@
 
 use super::consts::*;
 use super::http_client::*;
+use super::metrics::*;
 use super::toxic::*;
+use super::version::*;
+use super::wait::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::Duration;
 
 /// Raw info about a Proxy.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProxyPack {
     pub name: String,
     pub listen: String,
@@ -45,15 +50,233 @@ impl ProxyPack {
 }
 
 /// Client handler of the Proxy object.
+///
+/// The handler keeps the state of the proxy as last seen from the server: calls changing the
+/// proxy update it from the server's response, and [`refresh`] re-fetches it.
+///
+/// When the server is absent and the client passes traffic through (see [`AbsentServer`]), the
+/// proxy listens on its upstream address and its changes are not sent anywhere.
+///
+/// [`refresh`]: #method.refresh
+/// [`AbsentServer`]: ../absent_server/enum.AbsentServer.html
 #[derive(Debug)]
 pub struct Proxy {
+    /// The proxy as it was when the handler was created, never updated.
+    #[deprecated(
+        note = "a stale copy, use `proxy_pack()` or the other accessors for the state kept in \
+                sync with the server"
+    )]
     pub proxy_pack: ProxyPack,
-    client: Arc<Mutex<HttpClient>>,
+    name: String,
+    state: RwLock<ProxyPack>,
+    client: Arc<HttpClient>,
 }
 
 impl Proxy {
-    pub(crate) fn new(proxy_pack: ProxyPack, client: Arc<Mutex<HttpClient>>) -> Self {
-        Self { proxy_pack, client }
+    #[allow(deprecated)]
+    pub(crate) fn new(proxy_pack: ProxyPack, client: Arc<HttpClient>) -> Self {
+        Self {
+            name: proxy_pack.name.clone(),
+            state: RwLock::new(proxy_pack.clone()),
+            proxy_pack,
+            client,
+        }
+    }
+
+    /// Name of the proxy.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Address the proxy listens on, as last seen from the server.
+    pub fn listen(&self) -> String {
+        self.state().listen.clone()
+    }
+
+    /// Address of the service behind the proxy, as last seen from the server.
+    pub fn upstream(&self) -> String {
+        self.state().upstream.clone()
+    }
+
+    /// Address to connect to from this machine to go through the proxy: the listen address,
+    /// with an unspecified host (like `0.0.0.0` when listening on all interfaces) replaced by
+    /// the host of the Toxiproxy server.
+    pub fn connect_addr(&self) -> Result<String, String> {
+        let listen = self.listen();
+
+        match listen.parse::<SocketAddr>() {
+            Ok(addr) if addr.ip().is_unspecified() => {
+                let server_host = self
+                    .client
+                    .base_url()?
+                    .host_str()
+                    .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+                    .ok_or("Toxiproxy server address has no host")?
+                    .to_owned();
+
+                Ok(match server_host.parse::<IpAddr>() {
+                    Ok(ip) => SocketAddr::new(ip, addr.port()).to_string(),
+                    Err(_) => format!("{}:{}", server_host, addr.port()),
+                })
+            }
+            _ => Ok(listen),
+        }
+    }
+
+    /// Blocks until the proxy accepts connections on its listen address, for at most
+    /// `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .wait_until_listening(Duration::from_secs(5))
+    ///   .expect("proxy is listening");
+    /// ```
+    pub fn wait_until_listening(&self, timeout: Duration) -> Result<(), String> {
+        let what = format!("proxy `{}` is not listening", self.name);
+
+        wait_until(timeout, &what, |remaining| {
+            connect(&self.connect_addr()?, remaining).map(|_| ())
+        })
+    }
+
+    /// Blocks until the service behind the proxy accepts connections, for at most `timeout`.
+    /// The upstream address is the one the Toxiproxy server connects to, so it is checked
+    /// from this machine - when the server runs elsewhere (like in a container) the address
+    /// has to be reachable from both.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .wait_for_upstream(Duration::from_secs(5))
+    ///   .expect("service is up");
+    /// ```
+    pub fn wait_for_upstream(&self, timeout: Duration) -> Result<(), String> {
+        let what = format!("upstream of proxy `{}` is not reachable", self.name);
+        let upstream = self.upstream();
+
+        wait_until(timeout, &what, |remaining| {
+            connect(&upstream, remaining).map(|_| ())
+        })
+    }
+
+    /// Whether the proxy is enabled, as last seen from the server. To read the live state
+    /// combine it with [`refresh`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("socket").unwrap();
+    /// let is_enabled = proxy.refresh().unwrap().is_enabled();
+    /// ```
+    ///
+    /// [`refresh`]: #method.refresh
+    pub fn is_enabled(&self) -> bool {
+        self.state().enabled
+    }
+
+    /// Copy of the proxy config and its toxics, as last seen from the server.
+    pub fn proxy_pack(&self) -> ProxyPack {
+        self.state().clone()
+    }
+
+    /// Re-fetches the state of the proxy from the server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("socket").unwrap();
+    /// let toxic_count = proxy.refresh().unwrap().proxy_pack().toxics.len();
+    /// ```
+    pub fn refresh(&self) -> Result<&Self, String> {
+        if self.client.passes_through() {
+            return Ok(self);
+        }
+
+        let path = format!("proxies/{}", self.name);
+
+        self.client
+            .get(&path)
+            .and_then(|response| {
+                response
+                    .json()
+                    .map_err(|err| format!("json deserialize failed: {}", err))
+            })
+            .map(|proxy_pack| {
+                self.set_state(proxy_pack);
+                self
+            })
+    }
+
+    /// Traffic counters of the proxy since the server started, see [`Client::metrics`].
+    /// Take a snapshot before and after the code under test to count what went through.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_proxy("socket").unwrap();
+    /// if let Ok(stats) = proxy.stats() {
+    ///     let requested = stats.upstream.received_bytes;
+    /// }
+    /// ```
+    ///
+    /// [`Client::metrics`]: ../client/struct.Client.html#method.metrics
+    pub fn stats(&self) -> Result<ProxyStats, String> {
+        if self.client.passes_through() {
+            return Ok(ProxyStats::default());
+        }
+
+        Metrics::fetch(&self.client).map(|metrics| metrics.proxy(&self.name))
+    }
+
+    fn state(&self) -> RwLockReadGuard<'_, ProxyPack> {
+        // The state is replaced as a whole, a panic elsewhere cannot leave it half updated.
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_state(&self, proxy_pack: ProxyPack) {
+        *self.state.write().unwrap_or_else(PoisonError::into_inner) = proxy_pack;
+    }
+
+    fn update_toxics<F>(&self, update: F)
+    where
+        F: FnOnce(&mut Vec<ToxicPack>),
+    {
+        update(
+            &mut self
+                .state
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .toxics,
+        );
     }
 
     /// Disables the proxy - making all connections running through them fail immediately.
@@ -97,13 +320,26 @@ impl Proxy {
     }
 
     fn update(&self, payload: String) -> Result<(), String> {
-        let path = format!("proxies/{}", self.proxy_pack.name);
+        if self.client.passes_through() {
+            return Ok(());
+        }
 
-        self.client
-            .lock()
-            .map_err(|err| format!("lock error: {}", err))?
-            .post_with_data(&path, payload)
-            .map(|_| ())
+        let path = format!("proxies/{}", self.name);
+        // Servers that support PATCH still accept POST, so a version that cannot be fetched
+        // doesn't get in the way.
+        let response = if self.client.supports(Capability::PatchUpdates) == Ok(true) {
+            self.client.patch_with_data(&path, payload)
+        } else {
+            self.client.post_with_data(&path, payload)
+        };
+
+        response
+            .and_then(|response| {
+                response
+                    .json()
+                    .map_err(|err| format!("json deserialize failed: {}", err))
+            })
+            .map(|proxy_pack| self.set_state(proxy_pack))
     }
 
     /// Removes the proxy and all of its toxics.
@@ -119,16 +355,17 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY.find_proxy("socket").unwrap().delete();
     /// ```
     pub fn delete(&self) -> Result<(), String> {
-        let path = format!("proxies/{}", self.proxy_pack.name);
+        if self.client.passes_through() {
+            self.client.pass_through.remove(&self.name);
+            return Ok(());
+        }
 
-        self.client
-            .lock()
-            .map_err(|err| format!("lock error: {}", err))?
-            .delete(&path)
-            .map(|_| ())
+        let path = format!("proxies/{}", self.name);
+
+        self.client.delete(&path).map(|_| ())
     }
 
-    /// Retrieve all toxics registered on the proxy.
+    /// Retrieve all toxics registered on the proxy. The state of the handler is updated with them.
     ///
     /// # Examples
     ///
@@ -141,17 +378,20 @@ impl Proxy {
     /// let toxics = toxiproxy_rust::TOXIPROXY.find_proxy("socket").unwrap().toxics().unwrap();
     /// ```
     pub fn toxics(&self) -> Result<Vec<ToxicPack>, String> {
-        let path = format!("proxies/{}/toxics", self.proxy_pack.name);
+        if self.client.passes_through() {
+            return Ok(vec![]);
+        }
+
+        let path = format!("proxies/{}/toxics", self.name);
 
         self.client
-            .lock()
-            .map_err(|err| format!("lock error: {}", err))?
             .get(&path)
             .and_then(|response| {
                 response
-                    .json()
+                    .json::<Vec<ToxicPack>>()
                     .map_err(|err| format!("json deserialize failed: {}", err))
             })
+            .inspect(|toxics| self.update_toxics(|cached| *cached = toxics.clone()))
     }
 
     /// Registers a [latency] Toxic.
@@ -167,20 +407,20 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_latency("downstream".into(), 2000, 0, 1.0);
+    ///   .with_latency(toxiproxy_rust::toxic::Stream::Downstream, 2000, 0, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [latency]: https://github.com/Shopify/toxiproxy#latency
     pub fn with_latency(
         &self,
-        stream: String,
+        stream: Stream,
         latency: ToxicValueType,
         jitter: ToxicValueType,
-        toxicity: f32,
+        toxicity: Toxicity,
     ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("latency".into(), latency);
-        attributes.insert("jitter".into(), jitter);
+        attributes.insert("latency".into(), latency.into());
+        attributes.insert("jitter".into(), jitter.into());
 
         self.create_toxic(ToxicPack::new(
             "latency".into(),
@@ -203,13 +443,18 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_bandwidth("downstream".into(), 500, 1.0);
+    ///   .with_bandwidth(toxiproxy_rust::toxic::Stream::Downstream, 500, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [bandwith]: https://github.com/Shopify/toxiproxy#bandwith
-    pub fn with_bandwidth(&self, stream: String, rate: ToxicValueType, toxicity: f32) -> &Self {
+    pub fn with_bandwidth(
+        &self,
+        stream: Stream,
+        rate: ToxicValueType,
+        toxicity: Toxicity,
+    ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("rate".into(), rate);
+        attributes.insert("rate".into(), rate.into());
 
         self.create_toxic(ToxicPack::new(
             "bandwidth".into(),
@@ -232,13 +477,18 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_slow_close("downstream".into(), 500, 1.0);
+    ///   .with_slow_close(toxiproxy_rust::toxic::Stream::Downstream, 500, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [slow_close]: https://github.com/Shopify/toxiproxy#slow_close
-    pub fn with_slow_close(&self, stream: String, delay: ToxicValueType, toxicity: f32) -> &Self {
+    pub fn with_slow_close(
+        &self,
+        stream: Stream,
+        delay: ToxicValueType,
+        toxicity: Toxicity,
+    ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("delay".into(), delay);
+        attributes.insert("delay".into(), delay.into());
 
         self.create_toxic(ToxicPack::new(
             "slow_close".into(),
@@ -261,13 +511,18 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_timeout("downstream".into(), 5000, 1.0);
+    ///   .with_timeout(toxiproxy_rust::toxic::Stream::Downstream, 5000, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [timeout]: https://github.com/Shopify/toxiproxy#timeout
-    pub fn with_timeout(&self, stream: String, timeout: ToxicValueType, toxicity: f32) -> &Self {
+    pub fn with_timeout(
+        &self,
+        stream: Stream,
+        timeout: ToxicValueType,
+        toxicity: Toxicity,
+    ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("timeout".into(), timeout);
+        attributes.insert("timeout".into(), timeout.into());
 
         self.create_toxic(ToxicPack::new(
             "timeout".into(),
@@ -290,22 +545,22 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_slicer("downstream".into(), 1024, 128, 500, 1.0);
+    ///   .with_slicer(toxiproxy_rust::toxic::Stream::Downstream, 1024, 128, 500, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [slicer]: https://github.com/Shopify/toxiproxy#slicer
     pub fn with_slicer(
         &self,
-        stream: String,
+        stream: Stream,
         average_size: ToxicValueType,
         size_variation: ToxicValueType,
         delay: ToxicValueType,
-        toxicity: f32,
+        toxicity: Toxicity,
     ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("average_size".into(), average_size);
-        attributes.insert("size_variation".into(), size_variation);
-        attributes.insert("delay".into(), delay);
+        attributes.insert("average_size".into(), average_size.into());
+        attributes.insert("size_variation".into(), size_variation.into());
+        attributes.insert("delay".into(), delay.into());
 
         self.create_toxic(ToxicPack::new(
             "slicer".into(),
@@ -328,13 +583,18 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_limit_data("downstream".into(), 2048, 1.0);
+    ///   .with_limit_data(toxiproxy_rust::toxic::Stream::Downstream, 2048, toxiproxy_rust::toxic::Toxicity::always());
     /// ```
     ///
     /// [limit_data]: https://github.com/Shopify/toxiproxy#limit_data
-    pub fn with_limit_data(&self, stream: String, bytes: ToxicValueType, toxicity: f32) -> &Self {
+    pub fn with_limit_data(
+        &self,
+        stream: Stream,
+        bytes: ToxicValueType,
+        toxicity: Toxicity,
+    ) -> &Self {
         let mut attributes = HashMap::new();
-        attributes.insert("bytes".into(), bytes);
+        attributes.insert("bytes".into(), bytes.into());
 
         self.create_toxic(ToxicPack::new(
             "limit_data".into(),
@@ -344,18 +604,139 @@ impl Proxy {
         ))
     }
 
+    /// Registers a [reset_peer] Toxic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .with_reset_peer(toxiproxy_rust::toxic::Stream::Downstream, 500, toxiproxy_rust::toxic::Toxicity::always());
+    /// ```
+    ///
+    /// [reset_peer]: https://github.com/Shopify/toxiproxy#reset_peer
+    pub fn with_reset_peer(
+        &self,
+        stream: Stream,
+        timeout: ToxicValueType,
+        toxicity: Toxicity,
+    ) -> &Self {
+        let mut attributes = HashMap::new();
+        attributes.insert("timeout".into(), timeout.into());
+
+        self.create_toxic(ToxicPack::new(
+            "reset_peer".into(),
+            stream,
+            toxicity,
+            attributes,
+        ))
+    }
+
+    /// Registers a Toxic of any type, including custom toxics compiled into the server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let mut attributes = std::collections::HashMap::new();
+    /// attributes.insert("latency".into(), serde_json::json!(1000));
+    /// attributes.insert("jitter".into(), serde_json::json!(50));
+    ///
+    /// toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .with_custom_toxic("latency".into(), toxiproxy_rust::toxic::Stream::Upstream, toxiproxy_rust::toxic::Toxicity::always(), attributes);
+    /// ```
+    pub fn with_custom_toxic(
+        &self,
+        r#type: String,
+        stream: Stream,
+        toxicity: Toxicity,
+        attributes: ToxicAttributes,
+    ) -> &Self {
+        self.create_toxic(ToxicPack::new(r#type, stream, toxicity, attributes))
+    }
+
+    /// Registers a Toxic and returns the toxics created on the server. The config is
+    /// [validated] before anything is sent to the server. A Toxic on
+    /// [`Stream::Both`] is registered once per direction, so two toxics are returned for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use toxiproxy_rust::toxic::{Stream, ToxicPack, Toxicity};
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let mut attributes = HashMap::new();
+    /// attributes.insert("timeout".into(), 3000.into());
+    ///
+    /// let toxics = toxiproxy_rust::TOXIPROXY
+    ///   .find_proxy("socket")
+    ///   .unwrap()
+    ///   .add_toxic(ToxicPack::new("timeout".into(), Stream::Both, Toxicity::always(), attributes))
+    ///   .unwrap();
+    /// assert_eq!(2, toxics.len());
+    /// ```
+    ///
+    /// [validated]: ../toxic/struct.ToxicPack.html#method.validate
+    /// [`Stream::Both`]: ../toxic/enum.Stream.html#variant.Both
+    pub fn add_toxic(&self, toxic: ToxicPack) -> Result<Vec<ToxicPack>, String> {
+        toxic.validate()?;
+        if toxic.r#type == "reset_peer" {
+            self.client.require(Capability::ResetPeer)?;
+        }
+        if self.client.passes_through() {
+            return Ok(toxic.split());
+        }
+        let path = format!("proxies/{}/toxics", self.name);
+
+        let mut created = vec![];
+        for toxic in toxic.split() {
+            let result = serde_json::to_string(&toxic)
+                .map_err(|_| ERR_JSON_SERIALIZE.to_string())
+                .and_then(|body| self.client.post_with_data(&path, body))
+                .and_then(|response| {
+                    response
+                        .json::<ToxicPack>()
+                        .map_err(|err| format!("json deserialize failed: {}", err))
+                });
+
+            match result {
+                Ok(toxic) => {
+                    self.update_toxics(|cached| cached.push(toxic.clone()));
+                    created.push(toxic);
+                }
+                Err(err) => {
+                    // A `Stream::Both` toxic is added in both directions or in neither.
+                    for toxic in &created {
+                        let _ = self.delete_toxic(&toxic.name);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(created)
+    }
+
     fn create_toxic(&self, toxic: ToxicPack) -> &Self {
-        let body = serde_json::to_string(&toxic).expect(ERR_JSON_SERIALIZE);
-        let path = format!("proxies/{}/toxics", self.proxy_pack.name);
-
-        let _ = self
-            .client
-            .lock()
-            .expect(ERR_LOCK)
-            .post_with_data(&path, body)
-            .map_err(|err| {
-                panic!("<proxies>.<toxics> creation has failed: {}", err);
-            });
+        let _ = self.add_toxic(toxic).map_err(|err| {
+            panic!("<proxies>.<toxics> creation has failed: {}", err);
+        });
 
         self
     }
@@ -405,7 +786,7 @@ impl Proxy {
     /// toxiproxy_rust::TOXIPROXY
     ///   .find_proxy("socket")
     ///   .unwrap()
-    ///   .with_limit_data("downstream".into(), 2048, 1.0)
+    ///   .with_limit_data(toxiproxy_rust::toxic::Stream::Downstream, 2048, toxiproxy_rust::toxic::Toxicity::always())
     ///   .apply(|| {
     ///     /* Example test:
     ///        let service_result = MyService::Server::call(giant_payload);
@@ -441,15 +822,36 @@ impl Proxy {
     /// ```
     pub fn delete_all_toxics(&self) -> Result<(), String> {
         self.toxics().and_then(|toxic_list| {
-            for toxic in toxic_list {
-                let path = format!("proxies/{}/toxics/{}", self.proxy_pack.name, toxic.name);
-                self.client
-                    .lock()
-                    .map_err(|err| format!("lock error: {}", err))?
-                    .delete(&path)?;
-            }
-
-            Ok(())
+            toxic_list
+                .iter()
+                .try_for_each(|toxic| self.delete_toxic(&toxic.name))
         })
     }
+
+    /// Deletes a toxic by name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::toxic::{Stream, Toxicity};
+    /// # toxiproxy_rust::TOXIPROXY.populate(vec![toxiproxy_rust::proxy::ProxyPack::new(
+    /// #    "socket".into(),
+    /// #    "localhost:2001".into(),
+    /// #    "localhost:2000".into(),
+    /// # )]);
+    /// let proxy = toxiproxy_rust::TOXIPROXY.find_and_reset_proxy("socket").unwrap();
+    /// proxy.with_timeout(Stream::Upstream, 100, Toxicity::always());
+    /// proxy.delete_toxic("timeout_upstream").expect("toxic is deleted");
+    /// ```
+    pub fn delete_toxic(&self, toxic_name: &str) -> Result<(), String> {
+        if self.client.passes_through() {
+            return Ok(());
+        }
+
+        let path = format!("proxies/{}/toxics/{}", self.name, toxic_name);
+
+        self.client.delete(&path)?;
+        self.update_toxics(|cached| cached.retain(|cached| cached.name != toxic_name));
+        Ok(())
+    }
 }
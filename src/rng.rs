@@ -0,0 +1,56 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Small xorshift generator for toxicity and jitter, no need for a random crate.
+#[derive(Debug)]
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Self(hasher.finish() | 1)
+    }
+
+    /// A generator drawing the same numbers for the same seed.
+    pub(crate) fn from_seed(seed: u64) -> Self {
+        // Spreads the bits of small seeds, xorshift needs a non-zero state.
+        match seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) {
+            0 => Self(0x9e37_79b9_7f4a_7c15),
+            state => Self(state),
+        }
+    }
+
+    /// Another generator, seeded from this one.
+    pub(crate) fn fork(&mut self) -> Self {
+        Self(self.next() | 1)
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform in `[0, bound)`, 0 if `bound` is 0.
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next() % bound
+        }
+    }
+
+    /// Uniform in `[center - spread, center + spread]`, clamped to the range of `u64` for
+    /// spreads as large as the attributes allow.
+    pub(crate) fn around(&mut self, center: u64, spread: u64) -> u64 {
+        let offset = self.below(spread.saturating_mul(2).saturating_add(1));
+        center.saturating_add(offset).saturating_sub(spread)
+    }
+}
@@ -0,0 +1,137 @@
+//! Rewrites connection URLs of services (`redis://localhost:6379/0`,
+//! `postgres://user:secret@db:5432/app`, ...) to go through a proxy.
+
+use super::client::*;
+use super::proxy::*;
+use reqwest::Url;
+use std::net::{IpAddr, SocketAddr};
+
+/// Ports of common services, for URLs that don't name one.
+const DEFAULT_PORTS: &[(&str, u16)] = &[
+    ("redis", 6379),
+    ("rediss", 6379),
+    ("postgres", 5432),
+    ("postgresql", 5432),
+    ("mysql", 3306),
+    ("mariadb", 3306),
+    ("mongodb", 27017),
+    ("amqp", 5672),
+    ("amqps", 5671),
+    ("memcached", 11211),
+    ("nats", 4222),
+    ("kafka", 9092),
+    ("http", 80),
+    ("https", 443),
+];
+
+/// A service URL rewritten to the listen address of the proxy in front of the service.
+#[derive(Debug)]
+pub struct ProxiedUrl {
+    /// The URL with the host and port of the proxy, everything else kept as is.
+    pub url: String,
+    pub proxy: Proxy,
+}
+
+/// Finds the proxy for the host and port of `service_url`, creating it if needed, and
+/// rewrites the URL to its address.
+pub(crate) fn proxy_url(client: &Client, service_url: &str) -> Result<ProxiedUrl, String> {
+    let invalid = |reason: &str| format!("invalid service URL `{}`: {}", service_url, reason);
+
+    // Checked before parsing, which would reject the ports of the extra hosts instead.
+    if split_authority(service_url).is_some_and(|(_, authority, _)| authority.contains(',')) {
+        return Err(invalid("URLs with several hosts are not supported"));
+    }
+
+    let mut url = Url::parse(service_url).map_err(|err| invalid(&err.to_string()))?;
+    let host = match url.host_str() {
+        Some(host) if !host.is_empty() => host.to_owned(),
+        _ => return Err(invalid("missing host")),
+    };
+    let port = url
+        .port()
+        .or_else(|| default_port(url.scheme()))
+        .ok_or_else(|| invalid("missing port"))?;
+
+    let upstream = format!("{}:{}", host, port);
+    let name = proxy_name(url.scheme(), &host, port);
+    let proxy = match client.find_proxy(&name) {
+        Ok(proxy) if proxy.upstream() == upstream => proxy,
+        _ => client
+            .populate(vec![ProxyPack::new(
+                name.clone(),
+                "127.0.0.1:0".into(),
+                upstream,
+            )])?
+            .into_iter()
+            .find(|proxy| proxy.name() == name)
+            .ok_or_else(|| format!("proxy `{}` was not created", name))?,
+    };
+
+    let listen = proxy.connect_addr()?;
+    let (listen_host, listen_port) = match listen.parse::<SocketAddr>() {
+        Ok(addr) => (ip_host(addr.ip()), addr.port()),
+        Err(_) => {
+            let (listen_host, listen_port) = listen
+                .rsplit_once(':')
+                .ok_or_else(|| format!("invalid listen address `{}`", listen))?;
+            let listen_port = listen_port
+                .parse::<u16>()
+                .map_err(|_| format!("invalid listen address `{}`", listen))?;
+            (listen_host.to_owned(), listen_port)
+        }
+    };
+
+    // The host and port are replaced in the URL as given, since serializing the parsed one
+    // would normalize it, like adding a `/` path to `http://host:port`.
+    let listen = format!("{}:{}", listen_host, listen_port);
+    let url = match split_authority(service_url) {
+        Some((start, authority, rest)) => match authority.rsplit_once('@') {
+            Some((user_info, _)) => format!("{}{}@{}{}", start, user_info, listen, rest),
+            None => format!("{}{}{}", start, listen, rest),
+        },
+        None => {
+            url.set_host(Some(&listen_host))
+                .map_err(|err| invalid(&format!("cannot use proxy host: {}", err)))?;
+            url.set_port(Some(listen_port))
+                .map_err(|_| invalid("cannot use proxy port"))?;
+            url.to_string()
+        }
+    };
+
+    Ok(ProxiedUrl { url, proxy })
+}
+
+/// Splits a URL around its `user:password@host:port` part: what comes before it, the part
+/// itself and what follows. `None` if the URL has no `://`.
+fn split_authority(url: &str) -> Option<(&str, &str, &str)> {
+    let start = url.find("://")? + "://".len();
+    let end = url[start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |end| start + end);
+
+    Some((&url[..start], &url[start..end], &url[end..]))
+}
+
+fn default_port(scheme: &str) -> Option<u16> {
+    DEFAULT_PORTS
+        .iter()
+        .find(|(known_scheme, _)| *known_scheme == scheme)
+        .map(|(_, port)| *port)
+}
+
+/// Name of the proxy of a service, like `redis_localhost_6379`.
+fn proxy_name(scheme: &str, host: &str, port: u16) -> String {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+
+    format!("{}_{}_{}", scheme, host, port)
+}
+
+fn ip_host(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    }
+}
@@ -0,0 +1,196 @@
+//! [proptest] strategies generating valid toxics and fault combinations, enabled by the
+//! `proptest` feature.
+//!
+//! Shrinking heads towards the simplest fault: a failing case shrinks to fewer faults, to
+//! proxies taken down or toxics of the simplest types, to toxics applied to every connection
+//! and in one direction, and to the mildest attribute values (low latency, high bandwidth,
+//! large data limits...). The case that is left is the smallest one still failing.
+//!
+//! # Examples
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use toxiproxy_rust::strategy;
+//!
+//! proptest!(|(faults in strategy::faults(&["redis", "db"], 3))| {
+//!     /* apply the faults with a batch, then check the service */
+//!     prop_assert!(faults.len() <= 3);
+//! });
+//! ```
+//!
+//! [proptest]: https://docs.rs/proptest
+
+use super::fault_matrix::*;
+use super::toxic::*;
+use proptest::collection;
+use proptest::prelude::*;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+/// Types of the toxics generated by [`toxic`], simplest first.
+///
+/// [`toxic`]: fn.toxic.html
+pub const TOXIC_TYPES: &[&str] = &[
+    "latency",
+    "timeout",
+    "bandwidth",
+    "slow_close",
+    "limit_data",
+    "slicer",
+    "reset_peer",
+];
+
+/// Probability of a toxic, shrinking to `Toxicity::always()`.
+pub fn toxicity() -> impl Strategy<Value = Toxicity> {
+    prop_oneof![
+        Just(Toxicity::always()),
+        (1u8..=100).prop_map(Toxicity::percent),
+    ]
+}
+
+/// Direction of a toxic, shrinking to `Stream::Downstream`.
+pub fn stream() -> impl Strategy<Value = Stream> {
+    prop_oneof![
+        Just(Stream::Downstream),
+        Just(Stream::Upstream),
+        Just(Stream::Both),
+    ]
+}
+
+/// A toxic of any of the [`TOXIC_TYPES`].
+///
+/// [`TOXIC_TYPES`]: constant.TOXIC_TYPES.html
+pub fn toxic() -> impl Strategy<Value = ToxicPack> {
+    prop_oneof![
+        toxic_of("latency"),
+        toxic_of("timeout"),
+        toxic_of("bandwidth"),
+        toxic_of("slow_close"),
+        toxic_of("limit_data"),
+        toxic_of("slicer"),
+        toxic_of("reset_peer"),
+    ]
+}
+
+/// A toxic of the given type, with attributes in ranges that make a difference without
+/// stalling a test for long: delays up to 5 seconds, bandwidth from 1 to 1024 KB/s, data
+/// limits from 1 byte to 64 KB.
+///
+/// # Panics
+///
+/// Panics if the type is not one of the [`TOXIC_TYPES`].
+///
+/// [`TOXIC_TYPES`]: constant.TOXIC_TYPES.html
+pub fn toxic_of(r#type: &str) -> BoxedStrategy<ToxicPack> {
+    let attributes: BoxedStrategy<Vec<(&'static str, u32)>> = match r#type {
+        "latency" => (mild_low(0..=5000), mild_low(0..=1000))
+            .prop_map(|(latency, jitter)| vec![("latency", latency), ("jitter", jitter)])
+            .boxed(),
+        // A timeout of 0 holds the data forever, shorter ones fail faster.
+        "timeout" => mild_low(1..=5000)
+            .prop_map(|timeout| vec![("timeout", timeout)])
+            .boxed(),
+        "bandwidth" => mild_high(1..=1024)
+            .prop_map(|rate| vec![("rate", rate)])
+            .boxed(),
+        "slow_close" => mild_low(0..=5000)
+            .prop_map(|delay| vec![("delay", delay)])
+            .boxed(),
+        "limit_data" => mild_high(1..=64 * 1024)
+            .prop_map(|bytes| vec![("bytes", bytes)])
+            .boxed(),
+        "slicer" => (
+            mild_high(1..=4096),
+            mild_low(0..=1024),
+            mild_low(0..=10_000),
+        )
+            .prop_map(|(average_size, size_variation, delay)| {
+                // Slices cannot vary by more than their average size.
+                vec![
+                    ("average_size", average_size),
+                    ("size_variation", size_variation.min(average_size - 1)),
+                    ("delay", delay),
+                ]
+            })
+            .boxed(),
+        "reset_peer" => mild_high(0..=5000)
+            .prop_map(|timeout| vec![("timeout", timeout)])
+            .boxed(),
+        _ => panic!(
+            "no strategy for toxics of type `{}`, expected one of {:?}",
+            r#type, TOXIC_TYPES
+        ),
+    };
+    let r#type = r#type.to_owned();
+
+    (stream(), toxicity(), attributes)
+        .prop_map(move |(stream, toxicity, attributes)| {
+            ToxicPack::new(
+                r#type.clone(),
+                stream,
+                toxicity,
+                attributes
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), value.into()))
+                    .collect::<ToxicAttributes>(),
+            )
+        })
+        .boxed()
+}
+
+/// A fault on one of `proxies`: the proxy taken down or a toxic added to it.
+///
+/// # Panics
+///
+/// Panics if `proxies` is empty.
+pub fn fault(proxies: &[&str]) -> impl Strategy<Value = Fault> {
+    assert!(!proxies.is_empty(), "faults need at least one proxy");
+    let proxies = proxies
+        .iter()
+        .map(|proxy| proxy.to_string())
+        .collect::<Vec<String>>();
+
+    // `false` is the simpler value, so faults shrink to taking the proxy down.
+    (prop::sample::select(proxies), any::<bool>(), toxic()).prop_map(
+        |(proxy, with_toxic, toxic)| {
+            if with_toxic {
+                Fault::Toxic(proxy, toxic)
+            } else {
+                Fault::Down(proxy)
+            }
+        },
+    )
+}
+
+/// Up to `max_len` faults on `proxies`, applicable together: each proxy is taken down at most
+/// once and gets at most one toxic of each type. Shrinks by removing faults.
+///
+/// # Panics
+///
+/// Panics if `proxies` is empty.
+pub fn faults(proxies: &[&str], max_len: usize) -> impl Strategy<Value = Vec<Fault>> {
+    collection::vec(fault(proxies), 0..=max_len).prop_map(|faults| {
+        let mut seen = HashSet::new();
+        faults
+            .into_iter()
+            .filter(|fault| {
+                let kind = match fault {
+                    Fault::Down(_) => String::new(),
+                    Fault::Toxic(_, toxic) => toxic.r#type.clone(),
+                };
+                seen.insert((fault.proxy().to_owned(), kind))
+            })
+            .collect()
+    })
+}
+
+/// Values shrinking to the low end of the range, for attributes where less is milder.
+fn mild_low(range: RangeInclusive<u32>) -> impl Strategy<Value = u32> {
+    range
+}
+
+/// Values shrinking to the high end of the range, for attributes where more is milder.
+fn mild_high(range: RangeInclusive<u32>) -> impl Strategy<Value = u32> {
+    let (low, high) = range.into_inner();
+    (0..=high - low).prop_map(move |offset| high - offset)
+}
@@ -0,0 +1,357 @@
+//! In-process upstream services to put behind a proxy in tests: a TCP echo, a byte sink and
+//! source with an optional rate limit, and a minimal HTTP responder.
+//!
+//! Each one listens on an ephemeral port of `127.0.0.1` and stops when dropped. The Toxiproxy
+//! server has to be able to reach this machine on that address.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::time::Duration;
+//! use toxiproxy_rust::{probe, testing::Upstream, TOXIPROXY};
+//!
+//! let echo = Upstream::echo().expect("echo server is started");
+//! let proxies = TOXIPROXY.populate(vec![echo.proxy_pack("echo")]).unwrap();
+//!
+//! probe::assert_latency_between(&proxies[0], Duration::from_millis(0), Duration::from_millis(500));
+//! # proxies[0].delete().unwrap();
+//! ```
+
+use super::proxy::ProxyPack;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Pause between two checks for new connections or a stop request.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Size of the chunks read and written by the services.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// What an [`Upstream`] does with its connections.
+///
+/// [`Upstream`]: struct.Upstream.html
+#[derive(Debug, Clone)]
+enum Behaviour {
+    Echo,
+    Sink { rate: Option<u64> },
+    Source { rate: Option<u64> },
+    Http { status: u16, body: String },
+}
+
+/// Counters shared with the connection threads.
+#[derive(Debug, Default)]
+struct Counters {
+    connections: AtomicU64,
+    received_bytes: AtomicU64,
+    sent_bytes: AtomicU64,
+}
+
+/// A service running in a background thread, until dropped.
+#[derive(Debug)]
+pub struct Upstream {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl Upstream {
+    /// Writes back everything it reads, the upstream [`probe`] expects for round trips.
+    ///
+    /// [`probe`]: ../probe/index.html
+    pub fn echo() -> Result<Self, String> {
+        Self::start(Behaviour::Echo)
+    }
+
+    /// Reads and discards everything, at most `rate` bytes per second if given. A slow sink
+    /// makes the proxy (and the client behind it) hit backpressure.
+    pub fn sink(rate: Option<u64>) -> Result<Self, String> {
+        Self::start(Behaviour::Sink { rate })
+    }
+
+    /// Writes zeroes to every connection until it is closed, at most `rate` bytes per second
+    /// if given.
+    pub fn source(rate: Option<u64>) -> Result<Self, String> {
+        Self::start(Behaviour::Source { rate })
+    }
+
+    /// Answers every HTTP request with `status` and `body`, closing the connection after the
+    /// response.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::testing::Upstream;
+    /// let http = Upstream::http(503, "down for maintenance").unwrap();
+    /// let proxy_pack = http.proxy_pack("api");
+    /// ```
+    pub fn http(status: u16, body: &str) -> Result<Self, String> {
+        Self::start(Behaviour::Http {
+            status,
+            body: body.to_owned(),
+        })
+    }
+
+    /// Address the service listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Config of a proxy in front of the service, listening on a port picked by the server.
+    pub fn proxy_pack(&self, name: &str) -> ProxyPack {
+        ProxyPack::new(name.into(), "127.0.0.1:0".into(), self.addr.to_string())
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connections(&self) -> u64 {
+        self.counters.connections.load(Ordering::SeqCst)
+    }
+
+    /// Bytes read from all connections so far.
+    pub fn received_bytes(&self) -> u64 {
+        self.counters.received_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Bytes written to all connections so far.
+    pub fn sent_bytes(&self) -> u64 {
+        self.counters.sent_bytes.load(Ordering::SeqCst)
+    }
+
+    fn start(behaviour: Behaviour) -> Result<Self, String> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .map_err(|err| format!("upstream cannot listen: {}", err))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|err| format!("upstream has no address: {}", err))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| format!("upstream cannot listen: {}", err))?;
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let counters = Arc::new(Counters::default());
+        let acceptor = {
+            let stopped = stopped.clone();
+            let counters = counters.clone();
+            thread::spawn(move || accept(listener, behaviour, stopped, counters))
+        };
+
+        Ok(Self {
+            addr,
+            stopped,
+            counters,
+            acceptor: Some(acceptor),
+        })
+    }
+}
+
+impl Drop for Upstream {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+    }
+}
+
+fn accept(
+    listener: TcpListener,
+    behaviour: Behaviour,
+    stopped: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+) {
+    while !stopped.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                counters.connections.fetch_add(1, Ordering::SeqCst);
+                let connection = Connection {
+                    stream,
+                    stopped: stopped.clone(),
+                    counters: counters.clone(),
+                };
+                let behaviour = behaviour.clone();
+                thread::spawn(move || {
+                    let _ = connection.serve(behaviour);
+                });
+            }
+            Err(_) => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// An accepted connection, served until the peer closes it or the upstream is stopped.
+struct Connection {
+    stream: TcpStream,
+    stopped: Arc<AtomicBool>,
+    counters: Arc<Counters>,
+}
+
+impl Connection {
+    fn serve(mut self, behaviour: Behaviour) -> io::Result<()> {
+        self.stream.set_nonblocking(false)?;
+        self.stream.set_read_timeout(Some(POLL_INTERVAL * 10))?;
+        self.stream.set_write_timeout(Some(POLL_INTERVAL * 10))?;
+
+        match behaviour {
+            Behaviour::Echo => self.echo(),
+            Behaviour::Sink { rate } => self.sink(rate),
+            Behaviour::Source { rate } => self.source(rate),
+            Behaviour::Http { status, body } => self.http(status, &body),
+        }
+    }
+
+    /// Reads a chunk, `None` once the peer has closed the connection or the upstream is
+    /// stopped.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        loop {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            match self.stream.read(buf) {
+                Ok(0) => return Ok(None),
+                Ok(read) => {
+                    self.counters
+                        .received_bytes
+                        .fetch_add(read as u64, Ordering::SeqCst);
+                    return Ok(Some(read));
+                }
+                Err(err) if is_retriable(&err) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Writes all of `buf`, waiting for the peer to read as long as the upstream runs.
+    fn write(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            match self.stream.write(buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.counters
+                        .sent_bytes
+                        .fetch_add(written as u64, Ordering::SeqCst);
+                    buf = &buf[written..];
+                }
+                Err(err) if is_retriable(&err) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    fn echo(&mut self) -> io::Result<()> {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        while let Some(read) = self.read(&mut buf)? {
+            self.write(&buf[..read])?;
+        }
+        Ok(())
+    }
+
+    fn sink(&mut self, rate: Option<u64>) -> io::Result<()> {
+        let mut buf = vec![0u8; chunk_size(rate)];
+        let mut limiter = RateLimiter::new(rate);
+        while let Some(read) = self.read(&mut buf)? {
+            limiter.wait(read);
+        }
+        Ok(())
+    }
+
+    fn source(&mut self, rate: Option<u64>) -> io::Result<()> {
+        let buf = vec![0u8; chunk_size(rate)];
+        let mut limiter = RateLimiter::new(rate);
+        while !self.stopped.load(Ordering::SeqCst) {
+            self.write(&buf)?;
+            limiter.wait(buf.len());
+        }
+        Ok(())
+    }
+
+    fn http(&mut self, status: u16, body: &str) -> io::Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match self.read(&mut buf)? {
+                Some(read) => request.extend_from_slice(&buf[..read]),
+                None => return Ok(()),
+            }
+        }
+
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason_phrase(status),
+            body.len(),
+            body
+        );
+        self.write(response.as_bytes())
+    }
+}
+
+/// Spreads transfers over time so they don't go faster than a rate.
+struct RateLimiter {
+    rate: Option<u64>,
+    start: Instant,
+    transferred: u64,
+}
+
+impl RateLimiter {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            rate,
+            start: Instant::now(),
+            transferred: 0,
+        }
+    }
+
+    /// Accounts for `bytes` more bytes, sleeping until the rate allows them.
+    fn wait(&mut self, bytes: usize) {
+        let rate = match self.rate {
+            Some(rate) if rate > 0 => rate,
+            _ => return,
+        };
+
+        self.transferred += bytes as u64;
+        let due = self.start + Duration::from_secs_f64(self.transferred as f64 / rate as f64);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+    }
+}
+
+/// Chunks of about a tenth of a second of traffic, so rate limited transfers stay smooth.
+fn chunk_size(rate: Option<u64>) -> usize {
+    match rate {
+        Some(rate) => (rate / 10).clamp(1, CHUNK_SIZE as u64) as usize,
+        None => CHUNK_SIZE,
+    }
+}
+
+fn is_retriable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+    )
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
@@ -0,0 +1,234 @@
+//! Network partitions between services connected through proxies.
+//!
+//! A [`Topology`] knows which proxy carries the traffic of each link: a link from `api` to
+//! `db` is the proxy `api` connects to in order to reach `db`. Partitioning the services into
+//! groups cuts the links between groups, and leaves the links inside a group (or touching a
+//! service not in any group) working.
+//!
+//! # Examples
+//!
+//! ```
+//! # use toxiproxy_rust::{proxy::ProxyPack, TOXIPROXY};
+//! let topology = TOXIPROXY
+//!     .topology()
+//!     .link("a", "b", ProxyPack::new("a_to_b".into(), "127.0.0.1:0".into(), "localhost:2000".into()))
+//!     .link("b", "c", ProxyPack::new("b_to_c".into(), "127.0.0.1:0".into(), "localhost:2000".into()))
+//!     .link("a", "c", ProxyPack::new("a_to_c".into(), "127.0.0.1:0".into(), "localhost:2000".into()));
+//! topology.populate().unwrap();
+//!
+//! // `a` and `b` can still talk, `c` is isolated.
+//! topology.partition(&[&["a", "b"], &["c"]]).unwrap();
+//! topology.heal().unwrap();
+//! # for proxy in topology.populate().unwrap() { proxy.delete().unwrap(); }
+//! ```
+//!
+//! [`Topology`]: struct.Topology.html
+
+use super::batch::*;
+use super::client::*;
+use super::proxy::*;
+use super::toxic::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, PoisonError};
+
+/// Name of the toxics cutting a link, followed by the stream. A proxy carrying several links
+/// gets one toxic per stream.
+const PARTITION_TOXIC: &str = "partition";
+
+/// How a link is cut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cut {
+    /// Disables the proxy: connections are refused and existing ones closed.
+    Disable,
+    /// Adds `timeout` toxics that stop the data without closing connections, the way a
+    /// network partition usually looks like. One-way partitions always cut links this way.
+    Timeout,
+}
+
+/// A link between two services, carried by a proxy.
+#[derive(Debug, Clone)]
+pub struct Link {
+    /// The service connecting through the proxy.
+    pub from: String,
+    /// The service behind the proxy.
+    pub to: String,
+    pub proxy_pack: ProxyPack,
+}
+
+/// Services and the proxies between them, created by [`Client::topology`].
+///
+/// [`Client::topology`]: ../client/struct.Client.html#method.topology
+#[derive(Debug)]
+pub struct Topology {
+    client: Client,
+    links: Vec<Link>,
+    cut: Cut,
+    partition: Mutex<Option<AppliedBatch>>,
+}
+
+impl Topology {
+    pub(crate) fn new(client: Client) -> Self {
+        Self {
+            client,
+            links: vec![],
+            cut: Cut::Timeout,
+            partition: Mutex::new(None),
+        }
+    }
+
+    /// Adds a link from a service to another, going through the proxy of `proxy_pack`.
+    pub fn link(mut self, from: &str, to: &str, proxy_pack: ProxyPack) -> Self {
+        self.links.push(Link {
+            from: from.into(),
+            to: to.into(),
+            proxy_pack,
+        });
+        self
+    }
+
+    /// How links are cut by [`partition`], `Cut::Timeout` by default.
+    ///
+    /// [`partition`]: #method.partition
+    pub fn cut(mut self, cut: Cut) -> Self {
+        self.cut = cut;
+        self
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Creates the proxies of all links on the server.
+    pub fn populate(&self) -> Result<Vec<Proxy>, String> {
+        self.client.populate(
+            self.links
+                .iter()
+                .map(|link| link.proxy_pack.clone())
+                .collect(),
+        )
+    }
+
+    /// Splits the services into groups that cannot reach each other, replacing the current
+    /// partition if any.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let topology = toxiproxy_rust::TOXIPROXY.topology();
+    /// topology.partition(&[&["a", "b"], &["c"]]).unwrap();
+    /// ```
+    pub fn partition(&self, groups: &[&[&str]]) -> Result<(), String> {
+        let group_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(index, group)| group.iter().map(move |service| (*service, index)))
+            .collect::<HashMap<&str, usize>>();
+
+        let crossing = |link: &Link| {
+            matches!(
+                (group_of.get(link.from.as_str()), group_of.get(link.to.as_str())),
+                (Some(from), Some(to)) if from != to
+            )
+        };
+
+        self.apply(|batch| {
+            let mut cut = HashSet::new();
+            self.links
+                .iter()
+                .filter(|link| crossing(link))
+                .fold(batch, |batch, link| match self.cut {
+                    Cut::Disable => batch.disable(&link.proxy_pack.name),
+                    Cut::Timeout => cut_stream(batch, link, Stream::Both, &mut cut),
+                })
+        })
+    }
+
+    /// Stops the traffic sent from the services of `from` to the services of `to`, while
+    /// the other direction keeps working. Replaces the current partition if any.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # let topology = toxiproxy_rust::TOXIPROXY.topology();
+    /// // `c` still hears from `a` and `b`, but they don't hear back.
+    /// topology.partition_one_way(&["c"], &["a", "b"]).unwrap();
+    /// ```
+    pub fn partition_one_way(&self, from: &[&str], to: &[&str]) -> Result<(), String> {
+        let sends = |sender: &str, receiver: &str| from.contains(&sender) && to.contains(&receiver);
+
+        self.apply(|batch| {
+            let mut cut = HashSet::new();
+            self.links.iter().fold(batch, |batch, link| {
+                // Requests flow upstream, from the connecting service to the one behind the
+                // proxy, and responses downstream.
+                if sends(&link.from, &link.to) {
+                    cut_stream(batch, link, Stream::Upstream, &mut cut)
+                } else if sends(&link.to, &link.from) {
+                    cut_stream(batch, link, Stream::Downstream, &mut cut)
+                } else {
+                    batch
+                }
+            })
+        })
+    }
+
+    /// Restores the links cut by the current partition. If some cannot be restored, they stay
+    /// part of the partition, so calling `heal` again retries them.
+    pub fn heal(&self) -> Result<(), String> {
+        let mut partition = self
+            .partition
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(applied) = partition.as_mut() {
+            applied.try_undo()?;
+        }
+        *partition = None;
+        Ok(())
+    }
+
+    /// Heals the current partition, then applies the batch built by `build`.
+    fn apply<F>(&self, build: F) -> Result<(), String>
+    where
+        F: FnOnce(Batch) -> Batch,
+    {
+        self.heal()?;
+        let applied = build(self.client.batch()).apply()?;
+
+        *self
+            .partition
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(applied);
+        Ok(())
+    }
+}
+
+/// Adds the toxics stopping the data of a link in the given direction, skipping the ones
+/// already in `cut` (as proxy and toxic names) for another link of the same proxy.
+fn cut_stream(
+    batch: Batch,
+    link: &Link,
+    stream: Stream,
+    cut: &mut HashSet<(String, String)>,
+) -> Batch {
+    let mut attributes = ToxicAttributes::new();
+    attributes.insert("timeout".into(), 0.into());
+
+    stream
+        .directions()
+        .into_iter()
+        .fold(batch, |batch, stream| {
+            let mut toxic = ToxicPack::new(
+                "timeout".into(),
+                stream,
+                Toxicity::always(),
+                attributes.clone(),
+            );
+            toxic.name = format!("{}_{}", PARTITION_TOXIC, stream);
+            if cut.insert((link.proxy_pack.name.clone(), toxic.name.clone())) {
+                batch.add_toxic(&link.proxy_pack.name, toxic)
+            } else {
+                batch
+            }
+        })
+}
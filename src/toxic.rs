@@ -3,26 +3,202 @@
 //! [Toxic]: https://github.com/Shopify/toxiproxy#toxics
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 
 pub type ToxicValueType = u32;
 
+/// Attributes of a Toxic. Built-in toxics only use integers, custom server-side toxics
+/// may use any JSON value.
+pub type ToxicAttributes = HashMap<String, Value>;
+
+/// Direction of the connection a Toxic is applied to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    /// Data sent from the client towards the upstream service.
+    Upstream,
+    /// Data sent from the upstream service back to the client.
+    Downstream,
+    /// Client side convenience: registers the same Toxic on both directions, as a pair
+    /// of separate server side toxics.
+    Both,
+}
+
+impl Stream {
+    /// The server side directions this stream is made of.
+    pub fn directions(self) -> Vec<Stream> {
+        match self {
+            Stream::Both => vec![Stream::Upstream, Stream::Downstream],
+            stream => vec![stream],
+        }
+    }
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stream::Upstream => write!(f, "upstream"),
+            Stream::Downstream => write!(f, "downstream"),
+            Stream::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// Probability of a Toxic being applied to a connection, between `0.0` and `1.0`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(try_from = "f32", into = "f32")]
+pub struct Toxicity(f32);
+
+impl Toxicity {
+    /// Creates a toxicity from a probability, rejecting anything outside of `0.0..=1.0`
+    /// (including NaN).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::toxic::Toxicity;
+    /// assert!(Toxicity::new(0.75).is_ok());
+    /// assert!(Toxicity::new(1.5).is_err());
+    /// assert!(Toxicity::new(f32::NAN).is_err());
+    /// ```
+    pub fn new(probability: f32) -> Result<Self, String> {
+        if (0.0..=1.0).contains(&probability) {
+            Ok(Self(probability))
+        } else {
+            Err(format!(
+                "invalid toxicity {}: must be between 0.0 and 1.0",
+                probability
+            ))
+        }
+    }
+
+    /// The Toxic applies to every connection.
+    pub fn always() -> Self {
+        Self(1.0)
+    }
+
+    /// The Toxic applies to no connection.
+    pub fn never() -> Self {
+        Self(0.0)
+    }
+
+    /// The Toxic applies to the given percentage of connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percent` is more than 100.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::toxic::Toxicity;
+    /// assert_eq!(0.25, Toxicity::percent(25).value());
+    /// ```
+    pub fn percent(percent: u8) -> Self {
+        assert!(percent <= 100, "toxicity percent must be at most 100");
+        Self(f32::from(percent) / 100.0)
+    }
+
+    /// The probability as a number between `0.0` and `1.0`.
+    pub fn value(self) -> f32 {
+        self.0
+    }
+}
+
+impl TryFrom<f32> for Toxicity {
+    type Error = String;
+
+    fn try_from(probability: f32) -> Result<Self, Self::Error> {
+        Self::new(probability)
+    }
+}
+
+impl From<Toxicity> for f32 {
+    fn from(toxicity: Toxicity) -> Self {
+        toxicity.0
+    }
+}
+
+/// Shape an attribute value of a built-in Toxic has to follow.
+#[derive(Debug, Clone, Copy)]
+enum AttributeKind {
+    /// Durations, sizes and rates - the server reads them as non-negative integers.
+    Unsigned,
+}
+
+impl AttributeKind {
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            AttributeKind::Unsigned => value.as_u64().is_some(),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            AttributeKind::Unsigned => "a non-negative integer",
+        }
+    }
+}
+
+/// Attributes accepted by the built-in Toxic types, `None` for types unknown to the client
+/// (for example custom toxics compiled into the server).
+fn attribute_schema(r#type: &str) -> Option<&'static [(&'static str, AttributeKind)]> {
+    use AttributeKind::*;
+
+    match r#type {
+        "latency" => Some(&[("latency", Unsigned), ("jitter", Unsigned)]),
+        "bandwidth" => Some(&[("rate", Unsigned)]),
+        "slow_close" => Some(&[("delay", Unsigned)]),
+        "timeout" => Some(&[("timeout", Unsigned)]),
+        "slicer" => Some(&[
+            ("average_size", Unsigned),
+            ("size_variation", Unsigned),
+            ("delay", Unsigned),
+        ]),
+        "limit_data" => Some(&[("bytes", Unsigned)]),
+        "reset_peer" => Some(&[("timeout", Unsigned)]),
+        _ => None,
+    }
+}
+
 /// Config of a Toxic.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ToxicPack {
     pub name: String,
     pub r#type: String,
-    pub stream: String,
-    pub toxicity: f32,
-    pub attributes: HashMap<String, ToxicValueType>,
+    pub stream: Stream,
+    pub toxicity: Toxicity,
+    pub attributes: ToxicAttributes,
 }
 
 impl ToxicPack {
-    pub(crate) fn new(
+    /// Create a new Toxic configuration. The name is derived from the type and the stream,
+    /// the same way the server does it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use toxiproxy_rust::toxic::{Stream, ToxicPack, Toxicity};
+    /// let mut attributes = HashMap::new();
+    /// attributes.insert("rate".into(), 32.into());
+    ///
+    /// let toxic_pack = ToxicPack::new(
+    ///     "bandwidth".into(),
+    ///     Stream::Downstream,
+    ///     Toxicity::always(),
+    ///     attributes,
+    /// );
+    /// assert_eq!("bandwidth_downstream", toxic_pack.name);
+    /// ```
+    pub fn new(
         r#type: String,
-        stream: String,
-        toxicity: f32,
-        attributes: HashMap<String, ToxicValueType>,
+        stream: Stream,
+        toxicity: Toxicity,
+        attributes: ToxicAttributes,
     ) -> Self {
         let name = format!("{}_{}", r#type, stream);
         Self {
@@ -33,4 +209,89 @@ impl ToxicPack {
             attributes,
         }
     }
+
+    /// Checks the config against what the server accepts for the Toxic type, so an invalid
+    /// Toxic is rejected before reaching the server. Attributes of unknown (custom) types are
+    /// not checked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use toxiproxy_rust::toxic::{Stream, ToxicPack, Toxicity};
+    /// let mut attributes = HashMap::new();
+    /// attributes.insert("latency".into(), serde_json::json!(-100));
+    ///
+    /// let toxic_pack = ToxicPack::new(
+    ///     "latency".into(),
+    ///     Stream::Downstream,
+    ///     Toxicity::always(),
+    ///     attributes,
+    /// );
+    /// assert!(toxic_pack.validate().unwrap_err().contains("`latency`"));
+    /// ```
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() {
+            return Err(format!(
+                "invalid toxic of type `{}`: empty name",
+                self.r#type
+            ));
+        }
+
+        let schema = match attribute_schema(&self.r#type) {
+            Some(schema) => schema,
+            None => return Ok(()),
+        };
+
+        for (key, value) in &self.attributes {
+            let kind = schema
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, kind)| *kind)
+                .ok_or_else(|| {
+                    format!(
+                        "invalid toxic `{}`: unknown attribute `{}` for type `{}`",
+                        self.name, key, self.r#type
+                    )
+                })?;
+
+            if !kind.accepts(value) {
+                return Err(format!(
+                    "invalid toxic `{}`: attribute `{}` must be {}, got {}",
+                    self.name,
+                    key,
+                    kind.describe(),
+                    value
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Splits the config into one config per server side direction, named `{name}_upstream`
+    /// and `{name}_downstream`, or `{type}_upstream` and `{type}_downstream` if the name is
+    /// the default one.
+    pub(crate) fn split(self) -> Vec<ToxicPack> {
+        if self.stream != Stream::Both {
+            return vec![self];
+        }
+
+        let prefix = if self.name == format!("{}_{}", self.r#type, Stream::Both) {
+            &self.r#type
+        } else {
+            &self.name
+        };
+        self.stream
+            .directions()
+            .into_iter()
+            .map(|stream| ToxicPack {
+                name: format!("{}_{}", prefix, stream),
+                r#type: self.r#type.clone(),
+                stream,
+                toxicity: self.toxicity,
+                attributes: self.attributes.clone(),
+            })
+            .collect()
+    }
 }
@@ -0,0 +1,630 @@
+//! Toxics applied in-process to any stream, without a Toxiproxy server or sockets: wrap an
+//! in-memory pipe or a mock transport in a [`ToxicStream`] configured with the same
+//! `ToxicPack`s used against the server.
+//!
+//! The wrapped stream is seen from the client's side of a proxy: reads carry the downstream
+//! data and writes the upstream data, so a toxic on `Stream::Downstream` slows down or cuts
+//! reads. [`ToxicStream`] implements `std::io::Read` and `Write` for blocking streams, and
+//! `tokio::io::AsyncRead` and `AsyncWrite` with the `tokio` feature.
+//!
+//! The toxics behave like the server's, applied to each chunk of data going through:
+//!
+//! - `latency`: the chunk is delayed by `latency` milliseconds, give or take `jitter`
+//! - `bandwidth`: the chunk is delayed as if sent at `rate` KB/s
+//! - `slicer`: chunks are cut to about `average_size` bytes (give or take `size_variation`),
+//!   each one delayed by `delay` microseconds
+//! - `limit_data`: the stream is closed once `bytes` bytes went through
+//! - `timeout`: no data goes through, and the stream is closed after `timeout` milliseconds.
+//!   Writes are swallowed until then. With a `timeout` of 0 the stream is never closed: async
+//!   reads stay pending (bound them with `tokio::time::timeout`), and blocking reads fail with
+//!   `TimedOut` rather than hanging the thread
+//! - `reset_peer`: the stream fails with a connection reset after `timeout` milliseconds
+//! - `slow_close`: the end of stream is delayed by `delay` milliseconds, on reads when the
+//!   inner stream ends, and on writes when the stream is shut down (async streams only)
+//!
+//! As on the server, a toxic applies to the stream with the probability of its toxicity,
+//! decided when the stream is created.
+//!
+//! Delays are measured on a [`Clock`], real time unless [`ToxicStreamBuilder::clock`] sets
+//! another one: with a `VirtualClock`, streams don't wait for their toxics.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::{collections::HashMap, io::{Cursor, Read}, time::{Duration, Instant}};
+//! use toxiproxy_rust::toxic::{Stream, ToxicPack, Toxicity};
+//! use toxiproxy_rust::toxic_stream::ToxicStream;
+//!
+//! let mut attributes = HashMap::new();
+//! attributes.insert("latency".into(), 50.into());
+//! let latency = ToxicPack::new("latency".into(), Stream::Downstream, Toxicity::always(), attributes);
+//!
+//! let mut stream = ToxicStream::new(Cursor::new(b"hello".to_vec()), vec![latency]).unwrap();
+//! let started = Instant::now();
+//! let mut response = String::new();
+//! stream.read_to_string(&mut response).unwrap();
+//!
+//! assert_eq!("hello", response);
+//! assert!(started.elapsed() >= Duration::from_millis(50));
+//! ```
+//!
+//! [`ToxicStream`]: struct.ToxicStream.html
+//! [`Clock`]: ../clock/trait.Clock.html
+//! [`ToxicStreamBuilder::clock`]: struct.ToxicStreamBuilder.html#method.clock
+
+use super::clock::*;
+use super::rng::*;
+use super::toxic::*;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tokio")]
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Largest chunk read from the inner stream at once.
+const CHUNK_SIZE: usize = 32 * 1024;
+
+/// A stream with toxics applied to the data going through, see the [module docs].
+///
+/// [module docs]: index.html
+#[derive(Debug)]
+pub struct ToxicStream<S> {
+    inner: S,
+    clock: Arc<dyn Clock>,
+    read: ReadSide,
+    write: WriteSide,
+    #[cfg(feature = "tokio")]
+    read_alarm: Alarm,
+    #[cfg(feature = "tokio")]
+    write_alarm: Alarm,
+}
+
+impl<S> ToxicStream<S> {
+    /// Wraps `inner` with the toxics, on real time. Fails if a toxic is invalid or of a type
+    /// that cannot be applied in-process.
+    pub fn new(inner: S, toxics: Vec<ToxicPack>) -> Result<Self, String> {
+        Self::builder(inner, toxics).build()
+    }
+
+    /// Wraps `inner` with the toxics, with a clock or a seed set on the builder.
+    pub fn builder(inner: S, toxics: Vec<ToxicPack>) -> ToxicStreamBuilder<S> {
+        ToxicStreamBuilder {
+            inner,
+            toxics,
+            clock: Arc::new(SystemClock),
+            seed: None,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Builder of a [`ToxicStream`], created by [`ToxicStream::builder`].
+///
+/// [`ToxicStream`]: struct.ToxicStream.html
+/// [`ToxicStream::builder`]: struct.ToxicStream.html#method.builder
+#[derive(Debug)]
+pub struct ToxicStreamBuilder<S> {
+    inner: S,
+    toxics: Vec<ToxicPack>,
+    clock: Arc<dyn Clock>,
+    seed: Option<u64>,
+}
+
+impl<S> ToxicStreamBuilder<S> {
+    /// Clock measuring the delays, `SystemClock` by default.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Seed of the random draws (toxicity, jitter, slice sizes), for the same draws on every
+    /// run. Random by default.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Fails if a toxic is invalid or of a type that cannot be applied in-process.
+    pub fn build(self) -> Result<ToxicStream<S>, String> {
+        let mut rng = self.seed.map_or_else(Rng::new, Rng::from_seed);
+        let mut downstream = Toxics::default();
+        let mut upstream = Toxics::default();
+
+        for toxic in self.toxics {
+            toxic.validate()?;
+            for toxic in toxic.split() {
+                if rng.next_f32() >= toxic.toxicity.value() {
+                    continue;
+                }
+                match toxic.stream {
+                    Stream::Upstream => upstream.add(&toxic)?,
+                    _ => downstream.add(&toxic)?,
+                }
+            }
+        }
+
+        let started = self.clock.now();
+        Ok(ToxicStream {
+            inner: self.inner,
+            clock: self.clock,
+            read: ReadSide {
+                direction: Direction::new(downstream, started, rng.fork()),
+                staged: None,
+                ended: false,
+            },
+            write: WriteSide {
+                direction: Direction::new(upstream, started, rng),
+                pending: None,
+                #[cfg(feature = "tokio")]
+                close_at: None,
+            },
+            #[cfg(feature = "tokio")]
+            read_alarm: Alarm::default(),
+            #[cfg(feature = "tokio")]
+            write_alarm: Alarm::default(),
+        })
+    }
+}
+
+impl<S: Read> Read for ToxicStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.read.next(self.clock.now()) {
+                ReadStep::Wait(until) => self.clock.sleep_until(until),
+                ReadStep::WaitForever => return Err(io_error(io::ErrorKind::TimedOut)),
+                ReadStep::Deliver => return Ok(self.read.deliver(buf)),
+                ReadStep::Fail(kind) => return Err(io_error(kind)),
+                ReadStep::ReadInner(max) => {
+                    let mut chunk = vec![0; max];
+                    let read = self.inner.read(&mut chunk)?;
+                    chunk.truncate(read);
+                    self.read.received(chunk, self.clock.now());
+                }
+            }
+        }
+    }
+}
+
+impl<S: Write> Write for ToxicStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.write.next(self.clock.now(), buf.len()) {
+                WriteStep::Wait(until) => self.clock.sleep_until(until),
+                WriteStep::Swallow => return Ok(buf.len()),
+                WriteStep::Fail(kind) => return Err(io_error(kind)),
+                WriteStep::WriteInner(max) => {
+                    let written = self.inner.write(&buf[..max])?;
+                    self.write.written(written);
+                    return Ok(written);
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<S: AsyncRead + Unpin> AsyncRead for ToxicStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        loop {
+            match this.read.next(this.clock.now()) {
+                ReadStep::Wait(until) => {
+                    if this
+                        .read_alarm
+                        .poll_until(this.clock.as_ref(), until, cx)
+                        .is_pending()
+                    {
+                        return Poll::Pending;
+                    }
+                }
+                // Nothing will ever be read, so there is no waker to keep: the read stays
+                // pending until its future is dropped, like a peer that stopped sending.
+                ReadStep::WaitForever => return Poll::Pending,
+                ReadStep::Deliver => {
+                    let delivered = this.read.deliver(buf.initialize_unfilled());
+                    buf.advance(delivered);
+                    return Poll::Ready(Ok(()));
+                }
+                ReadStep::Fail(kind) => return Poll::Ready(Err(io_error(kind))),
+                ReadStep::ReadInner(max) => {
+                    let mut chunk = vec![0; max];
+                    let mut chunk_buf = ReadBuf::new(&mut chunk);
+                    match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                        Poll::Ready(Ok(())) => {
+                            let read = chunk_buf.filled().len();
+                            chunk.truncate(read);
+                            this.read.received(chunk, this.clock.now());
+                        }
+                        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<S: AsyncWrite + Unpin> AsyncWrite for ToxicStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match this.write.next(this.clock.now(), buf.len()) {
+                WriteStep::Wait(until) => {
+                    if this
+                        .write_alarm
+                        .poll_until(this.clock.as_ref(), until, cx)
+                        .is_pending()
+                    {
+                        return Poll::Pending;
+                    }
+                }
+                WriteStep::Swallow => return Poll::Ready(Ok(buf.len())),
+                WriteStep::Fail(kind) => return Poll::Ready(Err(io_error(kind))),
+                WriteStep::WriteInner(max) => {
+                    return Pin::new(&mut this.inner)
+                        .poll_write(cx, &buf[..max])
+                        .map_ok(|written| {
+                            this.write.written(written);
+                            written
+                        });
+                }
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let close_at = this.write.close_at(this.clock.now());
+        if this
+            .write_alarm
+            .poll_until(this.clock.as_ref(), close_at, cx)
+            .is_pending()
+        {
+            return Poll::Pending;
+        }
+
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+fn io_error(kind: io::ErrorKind) -> io::Error {
+    let message = match kind {
+        io::ErrorKind::ConnectionReset => "connection reset by the reset_peer toxic",
+        io::ErrorKind::TimedOut => "no data ever goes through the timeout toxic",
+        _ => "connection closed by a toxic",
+    };
+    io::Error::new(kind, message)
+}
+
+/// The toxics of one direction, with their attributes.
+#[derive(Debug, Default)]
+struct Toxics {
+    /// Latency and jitter, in milliseconds.
+    latency: Option<(u64, u64)>,
+    /// Rate in KB/s.
+    bandwidth: Option<u64>,
+    /// Average size and variation in bytes, delay in microseconds.
+    slicer: Option<(u64, u64, u64)>,
+    limit_data: Option<u64>,
+    /// Milliseconds before closing, never if 0.
+    timeout: Option<u64>,
+    /// Milliseconds before resetting.
+    reset_peer: Option<u64>,
+    /// Milliseconds the end of stream is delayed.
+    slow_close: Option<u64>,
+}
+
+impl Toxics {
+    fn add(&mut self, toxic: &ToxicPack) -> Result<(), String> {
+        let attribute = |name: &str| {
+            toxic
+                .attributes
+                .get(name)
+                .and_then(|value| value.as_u64())
+                .unwrap_or_default()
+        };
+
+        match toxic.r#type.as_str() {
+            "latency" => self.latency = Some((attribute("latency"), attribute("jitter"))),
+            "bandwidth" => self.bandwidth = Some(attribute("rate")),
+            "slicer" => {
+                self.slicer = Some((
+                    attribute("average_size"),
+                    attribute("size_variation"),
+                    attribute("delay"),
+                ))
+            }
+            "limit_data" => self.limit_data = Some(attribute("bytes")),
+            "timeout" => self.timeout = Some(attribute("timeout")),
+            "reset_peer" => self.reset_peer = Some(attribute("timeout")),
+            "slow_close" => self.slow_close = Some(attribute("delay")),
+            other => {
+                return Err(format!(
+                    "toxic `{}` of type `{}` cannot be applied in-process",
+                    toxic.name, other
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// State of the toxics of one direction.
+#[derive(Debug)]
+struct Direction {
+    toxics: Toxics,
+    started: Instant,
+    transferred: u64,
+    rng: Rng,
+}
+
+/// What a direction allows next, once checked it was not reset.
+enum Gate {
+    Open,
+    Wait(Instant),
+    WaitForever,
+    Closed,
+}
+
+impl Direction {
+    fn new(toxics: Toxics, started: Instant, rng: Rng) -> Self {
+        Self {
+            toxics,
+            started,
+            transferred: 0,
+            rng,
+        }
+    }
+
+    fn gate(&self, now: Instant) -> Gate {
+        let after = |millis: u64| self.started + Duration::from_millis(millis);
+
+        match self.toxics.timeout {
+            Some(0) => return Gate::WaitForever,
+            Some(millis) if now >= after(millis) => return Gate::Closed,
+            Some(millis) => return Gate::Wait(after(millis)),
+            None => {}
+        }
+        if self
+            .toxics
+            .limit_data
+            .is_some_and(|limit| self.transferred >= limit)
+        {
+            return Gate::Closed;
+        }
+
+        Gate::Open
+    }
+
+    /// Whether the connection was reset, losing the data held back.
+    fn is_reset(&self, now: Instant) -> bool {
+        self.toxics
+            .reset_peer
+            .is_some_and(|millis| now >= self.started + Duration::from_millis(millis))
+    }
+
+    /// Largest chunk that can go through next, out of `wanted` bytes.
+    fn chunk_size(&mut self, wanted: usize) -> usize {
+        let mut size = wanted as u64;
+        if let Some((average_size, size_variation, _)) = self.toxics.slicer {
+            size = size.min(self.rng.around(average_size, size_variation).max(1));
+        }
+        if let Some(limit) = self.toxics.limit_data {
+            size = size.min(limit.saturating_sub(self.transferred));
+        }
+
+        size as usize
+    }
+
+    /// How long a chunk of `size` bytes is held back.
+    fn delay(&mut self, size: usize) -> Duration {
+        let mut delay = Duration::ZERO;
+        if let Some((latency, jitter)) = self.toxics.latency {
+            delay += Duration::from_millis(self.rng.around(latency, jitter));
+        }
+        if let Some(rate) = self.toxics.bandwidth.filter(|rate| *rate > 0) {
+            delay += Duration::from_micros(size as u64 * 1000 / rate);
+        }
+        if let Some((_, _, micros)) = self.toxics.slicer {
+            delay += Duration::from_micros(micros);
+        }
+        delay
+    }
+
+    fn slow_close(&self) -> Duration {
+        Duration::from_millis(self.toxics.slow_close.unwrap_or_default())
+    }
+}
+
+/// Data read from the inner stream, held back until its delay passed.
+#[derive(Debug)]
+struct Staged {
+    data: Vec<u8>,
+    position: usize,
+    ready_at: Instant,
+}
+
+#[derive(Debug)]
+struct ReadSide {
+    direction: Direction,
+    staged: Option<Staged>,
+    /// Whether the inner stream ended.
+    ended: bool,
+}
+
+enum ReadStep {
+    Wait(Instant),
+    WaitForever,
+    /// Staged data (or the end of stream) can be handed to the caller.
+    Deliver,
+    Fail(io::ErrorKind),
+    /// Read at most this many bytes from the inner stream.
+    ReadInner(usize),
+}
+
+impl ReadSide {
+    fn next(&mut self, now: Instant) -> ReadStep {
+        if self.direction.is_reset(now) {
+            return ReadStep::Fail(io::ErrorKind::ConnectionReset);
+        }
+        match &self.staged {
+            Some(staged) if now < staged.ready_at => return ReadStep::Wait(staged.ready_at),
+            Some(_) => return ReadStep::Deliver,
+            None if self.ended => return ReadStep::Deliver,
+            None => {}
+        }
+
+        match self.direction.gate(now) {
+            Gate::Open => {}
+            Gate::Wait(until) => return ReadStep::Wait(until),
+            Gate::WaitForever => return ReadStep::WaitForever,
+            Gate::Closed => return ReadStep::Deliver,
+        }
+
+        ReadStep::ReadInner(self.direction.chunk_size(CHUNK_SIZE))
+    }
+
+    /// Stages a chunk read from the inner stream, empty at the end of stream.
+    fn received(&mut self, data: Vec<u8>, now: Instant) {
+        let delay = if data.is_empty() {
+            self.ended = true;
+            self.direction.slow_close()
+        } else {
+            self.direction.transferred += data.len() as u64;
+            self.direction.delay(data.len())
+        };
+
+        self.staged = Some(Staged {
+            data,
+            position: 0,
+            ready_at: now + delay,
+        });
+    }
+
+    /// Copies staged data into `buf`, nothing at the end of stream.
+    fn deliver(&mut self, buf: &mut [u8]) -> usize {
+        let staged = match &mut self.staged {
+            Some(staged) => staged,
+            None => return 0,
+        };
+
+        let size = buf.len().min(staged.data.len() - staged.position);
+        buf[..size].copy_from_slice(&staged.data[staged.position..staged.position + size]);
+        staged.position += size;
+        if staged.position == staged.data.len() {
+            self.staged = None;
+        }
+        size
+    }
+}
+
+#[derive(Debug)]
+struct WriteSide {
+    direction: Direction,
+    /// Size of the chunk being written and when it can go through.
+    pending: Option<(usize, Instant)>,
+    #[cfg(feature = "tokio")]
+    close_at: Option<Instant>,
+}
+
+enum WriteStep {
+    Wait(Instant),
+    /// The data is accepted but doesn't go anywhere.
+    Swallow,
+    Fail(io::ErrorKind),
+    /// Write at most this many bytes to the inner stream.
+    WriteInner(usize),
+}
+
+impl WriteSide {
+    fn next(&mut self, now: Instant, wanted: usize) -> WriteStep {
+        if self.direction.is_reset(now) {
+            return WriteStep::Fail(io::ErrorKind::ConnectionReset);
+        }
+        if let Some((size, ready_at)) = self.pending {
+            return if now < ready_at {
+                WriteStep::Wait(ready_at)
+            } else {
+                WriteStep::WriteInner(size.min(wanted))
+            };
+        }
+
+        match self.direction.gate(now) {
+            Gate::Open => {}
+            Gate::Wait(_) | Gate::WaitForever => return WriteStep::Swallow,
+            Gate::Closed => return WriteStep::Fail(io::ErrorKind::BrokenPipe),
+        }
+
+        let size = self.direction.chunk_size(wanted);
+        let delay = self.direction.delay(size);
+        if delay.is_zero() {
+            return WriteStep::WriteInner(size);
+        }
+
+        self.pending = Some((size, now + delay));
+        WriteStep::Wait(now + delay)
+    }
+
+    fn written(&mut self, size: usize) {
+        self.pending = None;
+        self.direction.transferred += size as u64;
+    }
+
+    /// When a shutdown can reach the inner stream.
+    #[cfg(feature = "tokio")]
+    fn close_at(&mut self, now: Instant) -> Instant {
+        let slow_close = self.direction.slow_close();
+        *self.close_at.get_or_insert(now + slow_close)
+    }
+}
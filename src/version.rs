@@ -0,0 +1,120 @@
+//! Version of the Toxiproxy server, and the features it supports.
+
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Semantic version of a Toxiproxy server.
+///
+/// Servers report it as plain text (`2.1.4`) or, in newer releases, as JSON
+/// (`{"version": "2.12.0"}`); both are understood.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerVersion(semver::Version);
+
+impl ServerVersion {
+    /// Creates a release version.
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self(semver::Version::new(major, minor, patch))
+    }
+
+    /// Parses the body of the server's `/version` endpoint.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::version::ServerVersion;
+    /// assert_eq!(ServerVersion::new(2, 1, 4), ServerVersion::parse("2.1.4\n").unwrap());
+    /// assert_eq!(
+    ///     ServerVersion::new(2, 12, 0),
+    ///     ServerVersion::parse(r#"{"version": "v2.12.0"}"#).unwrap()
+    /// );
+    /// ```
+    pub fn parse(body: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct VersionBody {
+            version: String,
+        }
+
+        let body = body.trim();
+        let version = match serde_json::from_str::<VersionBody>(body) {
+            Ok(json) => json.version,
+            Err(_) => body.to_owned(),
+        };
+        let version = version.trim().trim_start_matches('v');
+
+        semver::Version::parse(version)
+            .map(Self)
+            .map_err(|err| format!("invalid server version `{}`: {}", version, err))
+    }
+
+    pub fn major(&self) -> u64 {
+        self.0.major
+    }
+
+    pub fn minor(&self) -> u64 {
+        self.0.minor
+    }
+
+    pub fn patch(&self) -> u64 {
+        self.0.patch
+    }
+
+    /// Whether a server of this version has the given feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use toxiproxy_rust::version::{Capability, ServerVersion};
+    /// assert!(!ServerVersion::new(2, 1, 3).supports(Capability::ResetPeer));
+    /// assert!(ServerVersion::new(2, 1, 4).supports(Capability::ResetPeer));
+    /// ```
+    pub fn supports(&self, capability: Capability) -> bool {
+        *self >= capability.since()
+    }
+}
+
+impl FromStr for ServerVersion {
+    type Err = String;
+
+    fn from_str(body: &str) -> Result<Self, Self::Err> {
+        Self::parse(body)
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Server features not available in every Toxiproxy release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// The `reset_peer` toxic.
+    ResetPeer,
+    /// The Prometheus `/metrics` endpoint.
+    Metrics,
+    /// Updating proxies and toxics with `PATCH` (older servers use `POST`).
+    PatchUpdates,
+}
+
+impl Capability {
+    /// First server version with the feature.
+    pub fn since(self) -> ServerVersion {
+        match self {
+            Capability::ResetPeer => ServerVersion::new(2, 1, 4),
+            Capability::Metrics => ServerVersion::new(2, 6, 0),
+            Capability::PatchUpdates => ServerVersion::new(2, 6, 0),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::ResetPeer => write!(f, "the reset_peer toxic"),
+            Capability::Metrics => write!(f, "the /metrics endpoint"),
+            Capability::PatchUpdates => write!(f, "PATCH updates"),
+        }
+    }
+}
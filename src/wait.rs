@@ -0,0 +1,45 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pause between two checks while waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `check` until it succeeds or `timeout` elapses, returning the last failure in the
+/// latter case.
+pub(crate) fn wait_until<F>(timeout: Duration, what: &str, mut check: F) -> Result<(), String>
+where
+    F: FnMut(Duration) -> Result<(), String>,
+{
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let err = match check(remaining.max(POLL_INTERVAL)) {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        if Instant::now() + POLL_INTERVAL > deadline {
+            return Err(format!("{} after {:?}: {}", what, timeout, err));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Opens a TCP connection to `addr`, resolving it on every call.
+pub(crate) fn connect(addr: &str, timeout: Duration) -> Result<TcpStream, String> {
+    let socket_addrs = addr
+        .to_socket_addrs()
+        .map_err(|err| format!("cannot resolve `{}`: {}", addr, err))?;
+
+    let mut last_err = format!("no address found for `{}`", addr);
+    for socket_addr in socket_addrs {
+        match TcpStream::connect_timeout(&socket_addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = format!("cannot connect to `{}`: {}", addr, err),
+        }
+    }
+
+    Err(last_err)
+}
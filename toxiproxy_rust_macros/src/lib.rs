@@ -0,0 +1,233 @@
+//! Test attribute macros for [toxiproxy_rust], enabled by its `macros` feature.
+//!
+//! [toxiproxy_rust]: https://docs.rs/toxiproxy_rust
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, parse_macro_input, Attribute, FnArg, Ident, ItemFn, LitStr, Pat, Token};
+
+/// Runs a test with its own set of proxies, created on the `TOXIPROXY` server before the body
+/// and deleted after it, even if the test panics.
+///
+/// Proxies are given as `name=upstream`, the upstream being a port on `localhost` or a
+/// `host:port` address. Each proxy is namespaced by the test (so tests running in parallel
+/// don't share proxies), listens on a port picked by the server, and starts without toxics.
+/// Arguments of the test named after a proxy receive its `&Proxy` handle.
+///
+/// When the server is not running and `TOXIPROXY_ABSENT_SERVER=skip`, the test returns before
+/// creating its proxies, see `toxiproxy_rust::skip_if_absent!`.
+///
+/// The attribute adds `#[test]` to synchronous tests. Async tests keep their runtime's
+/// attribute, placed after this one, and check for the server before the runtime starts.
+///
+/// ```ignore
+/// use toxiproxy_rust::{proxy::Proxy, toxiproxy_test};
+///
+/// #[toxiproxy_test(proxies = ["redis=6379", "postgres=db.local:5432"])]
+/// fn survives_slow_redis(redis: &Proxy, postgres: &Proxy) {
+///     let addr = redis.connect_addr().unwrap();
+///     /* ... */
+/// }
+///
+/// #[toxiproxy_test(proxies = ["redis=6379"])]
+/// #[tokio::test]
+/// async fn survives_slow_redis_async(redis: &Proxy) {
+///     /* ... */
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn toxiproxy_test(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as Args);
+    let item = parse_macro_input!(item as ItemFn);
+
+    expand(args, item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Arguments of the attribute: `proxies = ["name=upstream", ...]`.
+struct Args {
+    proxies: Vec<LitStr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut proxies = None;
+
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            match key.to_string().as_str() {
+                "proxies" => {
+                    let content;
+                    bracketed!(content in input);
+                    let list = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                    proxies = Some(list.into_iter().collect());
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("unknown argument `{}`, expected `proxies`", key),
+                    ))
+                }
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        Ok(Self {
+            proxies: proxies.ok_or_else(|| {
+                syn::Error::new(Span::call_site(), "missing `proxies = [\"name=upstream\"]`")
+            })?,
+        })
+    }
+}
+
+fn expand(args: Args, mut item: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    let mut names = Vec::new();
+    for spec in &args.proxies {
+        let value = spec.value();
+        match value.split_once('=') {
+            Some((name, upstream)) if !name.trim().is_empty() && !upstream.trim().is_empty() => {
+                names.push(name.trim().to_owned())
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    spec.span(),
+                    "expected a proxy as `name=port` or `name=host:port`",
+                ))
+            }
+        }
+    }
+
+    let mut bindings = Vec::new();
+    for input in &item.sig.inputs {
+        let typed = match input {
+            FnArg::Typed(typed) => typed,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "a toxiproxy test cannot take `self`",
+                ))
+            }
+        };
+        let ident = match &*typed.pat {
+            Pat::Ident(pat) => &pat.ident,
+            pat => {
+                return Err(syn::Error::new_spanned(
+                    pat,
+                    "expected an argument named after a proxy",
+                ))
+            }
+        };
+        let name = ident.to_string();
+        if !names.contains(&name) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("no proxy named `{}` in `proxies`", name),
+            ));
+        }
+
+        let pat = &typed.pat;
+        let ty = &typed.ty;
+        bindings.push(quote! {
+            let #pat: #ty = __toxiproxy_fixture.proxy(#name);
+        });
+    }
+
+    let is_async = item.sig.asyncness.is_some();
+    let has_test_attribute = item.attrs.iter().any(is_test_attribute);
+    if is_async && !has_test_attribute {
+        return Err(syn::Error::new_spanned(
+            item.sig.fn_token,
+            "async tests need a runtime test attribute, like `#[tokio::test]`, after `#[toxiproxy_test]`",
+        ));
+    }
+    let test_attribute = if has_test_attribute {
+        quote! {}
+    } else {
+        quote! { #[test] }
+    };
+
+    let fn_name = item.sig.ident.to_string();
+    let specs = &args.proxies;
+    let block = &item.block;
+    let fixture = quote! {
+        let __toxiproxy_fixture = ::toxiproxy_rust::fixture::Fixture::new(
+            &format!("{}::{}", module_path!(), #fn_name),
+            &[#(#specs),*],
+        )
+        .unwrap_or_else(|err| panic!("toxiproxy fixture cannot be set up: {}", err));
+        #(#bindings)*
+    };
+
+    item.sig.inputs.clear();
+    if !is_async {
+        item.block = syn::parse2(quote! {
+            {
+                ::toxiproxy_rust::skip_if_absent!();
+                #fixture
+
+                #block
+            }
+        })?;
+        let attrs = &item.attrs;
+        let vis = &item.vis;
+        let sig = &item.sig;
+        let block = &item.block;
+
+        return Ok(quote! {
+            #test_attribute
+            #(#attrs)*
+            #vis #sig #block
+        });
+    }
+
+    // The absence check blocks on the network, so async tests run it in a plain `#[test]`
+    // before calling into the runtime's test, which becomes an inner item.
+    item.block = syn::parse2(quote! {
+        {
+            #fixture
+
+            #block
+        }
+    })?;
+    let (runtime_attrs, attrs): (Vec<_>, Vec<_>) =
+        item.attrs.drain(..).partition(is_test_attribute);
+    let vis = &item.vis;
+    let ident = &item.sig.ident;
+    let output = &item.sig.output;
+    let sig = &item.sig;
+    let block = &item.block;
+
+    Ok(quote! {
+        #[test]
+        #[allow(unnameable_test_items)]
+        #(#attrs)*
+        #vis fn #ident() #output {
+            ::toxiproxy_rust::skip_if_absent!();
+
+            #(#runtime_attrs)*
+            #sig #block
+
+            #ident()
+        }
+    })
+}
+
+/// Whether the attribute already makes the function a test, like `#[test]` or
+/// `#[tokio::test]`.
+fn is_test_attribute(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "test")
+}
@@ -0,0 +1,19 @@
+use toxiproxy_rust::{proxy::Proxy, toxiproxy_test};
+
+#[toxiproxy_test(proxies = ["redis=6379", "db=db.local:5432"])]
+fn sync_test(redis: &Proxy, db: &Proxy) {
+    assert_ne!(redis.name(), db.name());
+}
+
+#[toxiproxy_test(proxies = ["redis=6379"])]
+fn result_test(redis: &Proxy) -> Result<(), String> {
+    redis.toxics().map(drop)
+}
+
+#[toxiproxy_test(proxies = ["redis=6379"])]
+#[tokio::test]
+async fn async_test(redis: &Proxy) {
+    assert!(redis.is_enabled());
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use toxiproxy_rust::toxiproxy_test;
+
+#[toxiproxy_test(proxies = ["redis=6379"])]
+fn unknown_proxy(postgres: &toxiproxy_rust::proxy::Proxy) {
+    assert!(postgres.is_enabled());
+}
+
+fn main() {}